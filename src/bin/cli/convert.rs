@@ -1,40 +1,63 @@
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+use crate::readings::Overrides;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Default)]
 pub struct Exercise {
+    // Identifies the exercise in the user model, so that editing an exercise keeps its history.
+    // Exercises without one are identified by their Chinese text.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
     pub segments: Vec<Segment>,
     pub english: String,
+    // Other correct translations of the sentence.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub translations: Vec<String>,
+    // Where the sentence comes from, like a book or a deck.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hsk_level: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
 }
 
 impl Exercise {
-    pub fn parse(input: &str, strict_segmentation: bool, lax_pinyin: bool) -> Option<(Self, &str)> {
-        let mut input = input.trim();
-        let mut chinese = None;
-        let mut pinyin = None;
-        let mut english = None;
-        for _ in 0..3 {
-            let (line, rest) = input.split_once('\n').unwrap_or((input, ""));
-            input = rest;
-            let (key, value) = line.split_once(':')?;
-            match key {
-                "Chinese" => chinese = Some(value.trim().to_string()),
-                "Pinyin" => pinyin = Some(value.trim().to_string()),
-                "English" => english = Some(value.trim().to_string()),
-                _ => return None,
-            }
+    #[cfg(test)]
+    /// An exercise of the given words and their pinyin, for tests.
+    pub fn from_segments(segments: &[(&str, &str)]) -> Self {
+        Exercise {
+            segments: segments
+                .iter()
+                .map(|(chinese, pinyin)| Segment {
+                    chinese: chinese.to_string(),
+                    pinyin: pinyin.to_string(),
+                    ..Segment::default()
+                })
+                .collect(),
+            ..Exercise::default()
         }
-        let chinese = chinese?;
-        let pinyin = pinyin?;
-        let english = english?;
-        Some((
-            Exercise {
-                segments: Segment::join_with(&chinese, &pinyin, strict_segmentation, lax_pinyin),
-                english,
-            },
-            input,
-        ))
     }
+
+    #[cfg(test)]
+    /// An exercise of the given words, for tests that don't look at their pinyin.
+    pub fn from_words(words: &[&str]) -> Self {
+        let segments = words.iter().map(|word| (*word, "x")).collect::<Vec<_>>();
+        Self::from_segments(&segments)
+    }
+
+    pub fn id(&self) -> String {
+        if self.id.is_empty() {
+            self.chinese()
+        } else {
+            self.id.clone()
+        }
+    }
+
     pub fn words(&self) -> Vec<&String> {
         let mut ws = self
             .segments
@@ -62,13 +85,312 @@ impl Exercise {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// One sentence of a sentence file or of an imported table. Without pinyin the readings are
+/// guessed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    // Line the sentence starts on, counting from 1.
+    pub line: usize,
+    pub chinese: String,
+    pub pinyin: Option<String>,
+    pub english: String,
+}
+
+impl Row {
+    // Parse one entry of a sentence file: a Chinese, an optional Pinyin and an English line in any
+    // order.
+    fn parse(lines: &[&str], line: usize) -> Result<Self, ConvertError> {
+        let malformed = || ConvertError {
+            line,
+            kind: ConvertErrorKind::Malformed {
+                text: lines.join("\n"),
+            },
+        };
+        let mut chinese = None;
+        let mut pinyin = None;
+        let mut english = None;
+        for line in lines {
+            let (key, value) = line.split_once(':').ok_or_else(malformed)?;
+            let value = Some(value.trim().to_string());
+            match key {
+                "Chinese" => chinese = value,
+                "Pinyin" => pinyin = value,
+                "English" => english = value,
+                _ => return Err(malformed()),
+            }
+        }
+        let (Some(chinese), Some(english)) = (chinese, english) else {
+            return Err(malformed());
+        };
+        Ok(Row {
+            line,
+            chinese,
+            pinyin,
+            english,
+        })
+    }
+
+    // Convert the sentence to an exercise. Also returns the words whose reading had to be guessed
+    // among several.
+    fn convert(
+        self,
+        strict_segmentation: bool,
+        lax_pinyin: bool,
+        overrides: &Overrides,
+    ) -> Result<(Exercise, Vec<Ambiguity>), ConvertError> {
+        let (segments, ambiguities) = match &self.pinyin {
+            Some(pinyin) => (
+                Segment::join_with(
+                    &self.chinese,
+                    pinyin,
+                    strict_segmentation,
+                    lax_pinyin,
+                    overrides,
+                )
+                .map_err(|kind| ConvertError {
+                    line: self.line,
+                    kind,
+                })?,
+                vec![],
+            ),
+            None => Segment::generate(&self.chinese, overrides),
+        };
+        let exercise = Exercise {
+            segments,
+            english: self.english,
+            ..Exercise::default()
+        };
+        Ok((exercise, ambiguities))
+    }
+}
+
+/// The exercises of a sentence file or of imported rows, in order. Entries that can't be
+/// converted are returned as errors and skipped, so the entries after them are still converted.
+pub struct SentenceFile<'a> {
+    rest: &'a str,
+    // Line of the sentence file `rest` starts on, counting from 1.
+    line: usize,
+    rows: std::vec::IntoIter<Result<Row, ConvertError>>,
+    strict_segmentation: bool,
+    lax_pinyin: bool,
+    overrides: &'a Overrides,
+    // Guessed readings to review, with the line of their entry.
+    ambiguities: Vec<(usize, Ambiguity)>,
+}
+
+impl<'a> SentenceFile<'a> {
+    pub fn new(
+        text: &'a str,
+        strict_segmentation: bool,
+        lax_pinyin: bool,
+        overrides: &'a Overrides,
+    ) -> Self {
+        SentenceFile {
+            rest: text,
+            line: 1,
+            rows: vec![].into_iter(),
+            strict_segmentation,
+            lax_pinyin,
+            overrides,
+            ambiguities: vec![],
+        }
+    }
+
+    /// Convert rows read from another format, like a table of sentences.
+    pub fn from_rows(
+        rows: Vec<Result<Row, ConvertError>>,
+        strict_segmentation: bool,
+        lax_pinyin: bool,
+        overrides: &'a Overrides,
+    ) -> Self {
+        SentenceFile {
+            rows: rows.into_iter(),
+            ..Self::new("", strict_segmentation, lax_pinyin, overrides)
+        }
+    }
+
+    /// Words of the entries converted so far whose reading was guessed among several, with the
+    /// line of their entry.
+    pub fn take_ambiguities(&mut self) -> Vec<(usize, Ambiguity)> {
+        std::mem::take(&mut self.ambiguities)
+    }
+
+    // Read the next entry of the sentence file text.
+    fn next_entry(&mut self) -> Option<Result<Row, ConvertError>> {
+        // Skip blank lines before the entry.
+        while let Some((line, rest)) = self.rest.split_once('\n') {
+            if !line.trim().is_empty() {
+                break;
+            }
+            self.rest = rest;
+            self.line += 1;
+        }
+        if self.rest.trim().is_empty() {
+            return None;
+        }
+
+        // An entry has three lines. It ends early at a blank line or when a key repeats, which
+        // means the next entry has started.
+        let line = self.line;
+        let mut lines: Vec<&str> = vec![];
+        while lines.len() < 3 && !self.rest.is_empty() {
+            let (next, rest) = self.rest.split_once('\n').unwrap_or((self.rest, ""));
+            let next = next.trim();
+            let key = next.split_once(':').map(|(key, _)| key);
+            if next.is_empty()
+                || key.is_some_and(|key| lines.iter().any(|l| l.starts_with(&format!("{key}:"))))
+            {
+                break;
+            }
+            lines.push(next);
+            self.rest = rest;
+            self.line += 1;
+        }
+        Some(Row::parse(&lines, line))
+    }
+}
+
+impl<'a> Iterator for SentenceFile<'a> {
+    type Item = Result<Exercise, ConvertError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = match self.rows.next() {
+            Some(row) => row,
+            None => self.next_entry()?,
+        };
+        Some(row.and_then(|row| {
+            let line = row.line;
+            let (exercise, ambiguities) =
+                row.convert(self.strict_segmentation, self.lax_pinyin, self.overrides)?;
+            self.ambiguities
+                .extend(ambiguities.into_iter().map(|ambiguity| (line, ambiguity)));
+            Ok(exercise)
+        }))
+    }
+}
+
+/// A sentence that could not be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertError {
+    // Line of the sentence file the entry starts on, counting from 1.
+    pub line: usize,
+    pub kind: ConvertErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConvertErrorKind {
+    // The entry isn't made of a Chinese, an optional Pinyin and an English line.
+    Malformed { text: String },
+    // None of the dictionary readings of the Chinese match the pinyin.
+    Alignment(Misalignment),
+    // A reading matched, but the pinyin carries on into the next syllable, so the pinyin splits
+    // the words differently than the dictionary does.
+    Segmentation(Misalignment),
+}
+
+/// Where the Chinese and the pinyin of a sentence stopped lining up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misalignment {
+    pub chinese: String,
+    pub pinyin: String,
+    // Characters of `chinese` and `pinyin` that were aligned before the failure.
+    pub chinese_position: usize,
+    pub pinyin_position: usize,
+    // Dictionary readings of the words starting at the failure.
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub word: String,
+    pub pinyin: String,
+}
+
+impl std::fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let misalignment = match &self.kind {
+            ConvertErrorKind::Malformed { text } => {
+                return write!(
+                    f,
+                    "line {}: expected Chinese, English and optionally Pinyin:\n{text}",
+                    self.line
+                );
+            }
+            ConvertErrorKind::Alignment(m) => {
+                let c = m.chinese.chars().nth(m.chinese_position).unwrap_or(' ');
+                writeln!(f, "line {}: failed to align pinyin at {c}", self.line)?;
+                m
+            }
+            ConvertErrorKind::Segmentation(m) => {
+                let c = m.chinese.chars().nth(m.chinese_position).unwrap_or(' ');
+                writeln!(f, "line {}: segmentation failed at {c}", self.line)?;
+                m
+            }
+        };
+        // Chinese characters are two columns wide in a terminal.
+        let chinese_width = misalignment
+            .chinese
+            .chars()
+            .take(misalignment.chinese_position)
+            .map(|c| if c.is_ascii() { 1 } else { 2 })
+            .sum::<usize>();
+        writeln!(f, "  Chinese: {}", misalignment.chinese)?;
+        writeln!(f, "           {}^", " ".repeat(chinese_width))?;
+        writeln!(f, "  Pinyin:  {}", misalignment.pinyin)?;
+        writeln!(
+            f,
+            "           {}^",
+            " ".repeat(misalignment.pinyin_position)
+        )?;
+        write!(
+            f,
+            "  Dictionary: {}",
+            misalignment
+                .candidates
+                .iter()
+                .map(|c| format!("{} {}", c.word, c.pinyin))
+                .join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+/// A word of a sentence without pinyin that has several readings. The first one was used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguity {
+    pub word: String,
+    pub readings: Vec<String>,
+}
+
+impl std::fmt::Display for Ambiguity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} read as {}, could also be {}",
+            self.word,
+            self.readings[0],
+            self.readings[1..].join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 pub struct Segment {
     pub chinese: String,
+    // The canonical reading, shown as the answer.
     pub pinyin: String,
+    // Other readings accepted as answers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternates: Vec<String>,
 }
 
 impl Segment {
+    /// Every accepted reading, the canonical one first.
+    pub fn readings(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.pinyin.as_str()).chain(self.alternates.iter().map(String::as_str))
+    }
+
     // 今天有两个会议。
     // Jīntiān yǒu liǎng gè huìyì.
     // 今天     有  两    个  会议。
@@ -78,84 +400,244 @@ impl Segment {
     //
     // Chinese: 我叫David。你好。
     // Pinyin: Wǒ jiào David. Nǐ hǎo.
+    #[cfg(test)]
     fn join(orig_chinese: &str, orig_pinyin: &str) -> Vec<Self> {
-        Self::join_with(orig_chinese, orig_pinyin, true, false)
+        Self::join_with(
+            orig_chinese,
+            orig_pinyin,
+            true,
+            false,
+            &Overrides::default(),
+        )
+        .unwrap()
     }
 
-    fn join_with(
+    /// Split a sentence into words and match each with its part of the pinyin. Words may be
+    /// written with any of their accepted readings, but get the dictionary or override reading.
+    pub fn join_with(
         orig_chinese: &str,
         orig_pinyin: &str,
         strict_segmentation: bool,
         lax_pinyin: bool,
-    ) -> Vec<Self> {
+        overrides: &Overrides,
+    ) -> Result<Vec<Self>, ConvertErrorKind> {
         let mut segments: Vec<Self> = vec![];
-        let pinyin = orig_pinyin.to_lowercase().replace("'", "");
-        let mut pinyin = pinyin.as_str();
+        let lower_pinyin = orig_pinyin.to_lowercase().replace('\'', "");
+        let mut pinyin = lower_pinyin.as_str();
         let orig_chinese = orig_chinese.replace(' ', "");
         let mut chinese = orig_chinese.as_str();
         'top: while !chinese.is_empty() {
             pinyin = pinyin.trim_start();
-            let results = haoxue_dict::DICTIONARY
+            let mut results = haoxue_dict::DICTIONARY
                 .lookup_entries(chinese)
+                .map(|e| (e.simplified(), prettify_pinyin::prettify(e.pinyin())))
                 .collect::<Vec<_>>();
+            results.extend(
+                overrides
+                    .prefixes(chinese)
+                    .map(|(word, reading)| (word, reading.to_string())),
+            );
+            // Longest words first. Overrides come before dictionary readings of the same word.
+            results.reverse();
+            results.sort_by_key(|(word, _)| Reverse(word.chars().count()));
             if results.is_empty() {
                 let (c, new_chinese) = str_pop(chinese).unwrap();
-                match segments.last_mut() {
-                    Some(s) if s.pinyin.is_empty() => {
-                        s.chinese += &c.to_string();
-                    }
-                    _ => {
-                        segments.push(Segment {
-                            chinese: c.to_string(),
-                            pinyin: "".to_string(),
-                        });
-                    }
-                }
+                push_unknown(&mut segments, c);
                 chinese = new_chinese;
-                pinyin = str_tail(&pinyin);
+                pinyin = str_tail(pinyin);
             } else {
+                let misalignment = || Misalignment {
+                    chinese: orig_chinese.clone(),
+                    pinyin: orig_pinyin.to_string(),
+                    chinese_position: orig_chinese.chars().count() - chinese.chars().count(),
+                    pinyin_position: original_position(
+                        orig_pinyin,
+                        lower_pinyin.chars().count() - pinyin.chars().count(),
+                    ),
+                    candidates: results
+                        .iter()
+                        .map(|(word, pinyin)| Candidate {
+                            word: word.to_string(),
+                            pinyin: pinyin.clone(),
+                        })
+                        .collect(),
+                };
                 let longest_result = results
                     .iter()
-                    .map(|e| e.simplified().chars().count())
+                    .map(|(word, _)| word.chars().count())
                     .max()
                     .unwrap_or_default();
                 let n_longest = results
                     .iter()
-                    .filter(|e| e.simplified().chars().count() == longest_result)
-                    .map(|e| e.pinyin())
+                    .filter(|(word, _)| word.chars().count() == longest_result)
+                    .map(|(_, pinyin)| pinyin)
                     .unique()
                     .count();
-                for (nth, entry) in results.iter().rev().enumerate() {
-                    let pretty = prettify_pinyin::prettify(entry.pinyin());
-                    let pretty_compact = pretty.to_lowercase().replace(" ", "");
-                    let stripped =
-                        if lax_pinyin && longest_result >= 2 && n_longest == 1 && nth == 0 {
-                            strip_prefix_no_tones(pinyin, &pretty_compact)
+                for (nth, (word, pretty)) in results.iter().enumerate() {
+                    let lax = lax_pinyin && longest_result >= 2 && n_longest == 1 && nth == 0;
+                    let stripped = overrides.variants(word, pretty).iter().find_map(|variant| {
+                        let compact = variant.to_lowercase().replace(' ', "");
+                        if lax {
+                            strip_prefix_no_tones(pinyin, &compact)
                         } else {
-                            pinyin.strip_prefix(pretty_compact.as_str())
-                        };
+                            pinyin.strip_prefix(compact.as_str())
+                        }
+                    });
                     if let Some(new_pinyin) = stripped {
-                        if strict_segmentation && new_pinyin.chars().next().unwrap().is_alphabetic()
+                        if strict_segmentation
+                            && new_pinyin.chars().next().is_some_and(char::is_alphabetic)
                         {
-                            panic!(
-                                "Segmentation failed at {} at {pinyin}",
-                                pretty.to_lowercase().replace(" ", "")
-                            );
+                            return Err(ConvertErrorKind::Segmentation(misalignment()));
                         }
+                        // Other dictionary readings of the word the pinyin could also be.
+                        let written = &pinyin[..pinyin.len() - new_pinyin.len()];
+                        let alternates = results
+                            .iter()
+                            .filter(|(other, reading)| other == word && reading != pretty)
+                            .filter(|(other, reading)| {
+                                overrides
+                                    .variants(other, reading)
+                                    .iter()
+                                    .any(|v| v.to_lowercase().replace(' ', "") == written)
+                            })
+                            .map(|(_, reading)| reading.clone())
+                            .unique()
+                            .collect();
                         segments.push(Segment {
-                            chinese: entry.simplified().to_string(),
-                            pinyin: pretty,
+                            chinese: word.to_string(),
+                            pinyin: pretty.clone(),
+                            alternates,
                         });
-                        chinese = chinese.strip_prefix(entry.simplified()).unwrap();
+                        chinese = chinese.strip_prefix(*word).unwrap();
                         pinyin = new_pinyin;
                         continue 'top;
                     }
                 }
-                panic!("Failed to align match {orig_chinese} with {orig_pinyin} at {pinyin}");
+                return Err(ConvertErrorKind::Alignment(misalignment()));
             }
         }
-        segments
+        Ok(segments)
+    }
+
+    /// Split a sentence that comes without pinyin into words and give each its most likely
+    /// reading. Returns the words whose reading was chosen among several, to be checked by hand.
+    pub fn generate(orig_chinese: &str, overrides: &Overrides) -> (Vec<Self>, Vec<Ambiguity>) {
+        let mut segments: Vec<Self> = vec![];
+        let mut ambiguities = vec![];
+        let orig_chinese = orig_chinese.replace(' ', "");
+        let mut chinese = orig_chinese.as_str();
+        while let Some((c, rest)) = str_pop(chinese) {
+            let Some(word) = likely_word(chinese, overrides) else {
+                push_unknown(&mut segments, c);
+                chinese = rest;
+                continue;
+            };
+            let readings = readings(word, overrides);
+            if readings.len() > 1 {
+                ambiguities.push(Ambiguity {
+                    word: word.to_string(),
+                    readings: readings.clone(),
+                });
+            }
+            // Like in join_with, only readings the chosen one is a way of writing are accepted too.
+            // The others are left for the user to choose between.
+            let chosen = readings[0].to_lowercase().replace(' ', "");
+            let alternates = readings[1..]
+                .iter()
+                .filter(|reading| {
+                    overrides
+                        .variants(word, reading)
+                        .iter()
+                        .any(|v| v.to_lowercase().replace(' ', "") == chosen)
+                })
+                .cloned()
+                .collect();
+            segments.push(Segment {
+                chinese: word.to_string(),
+                pinyin: readings[0].clone(),
+                alternates,
+            });
+            chinese = &chinese[word.len()..];
+        }
+        (segments, ambiguities)
+    }
+}
+
+// Text without a dictionary entry has no pinyin. Consecutive characters of it form one segment.
+fn push_unknown(segments: &mut Vec<Segment>, c: char) {
+    match segments.last_mut() {
+        Some(s) if s.pinyin.is_empty() => {
+            s.chinese.push(c);
+        }
+        _ => {
+            segments.push(Segment {
+                chinese: c.to_string(),
+                ..Segment::default()
+            });
+        }
+    }
+}
+
+// Words `text` starts with, shortest first.
+fn prefix_words<'a>(text: &'a str, overrides: &'a Overrides) -> Vec<&'a str> {
+    haoxue_dict::DICTIONARY
+        .lookup_entries(text)
+        .map(|e| e.simplified())
+        .chain(overrides.prefixes(text).map(|(word, _)| word))
+        .unique()
+        .sorted_by_key(|word| word.len())
+        .collect()
+}
+
+// The word `text` most likely starts with. Longer words are preferred, looking one word ahead so
+// that a long word doesn't leave a stray character behind. Ties go to the more frequent word.
+//
+// likely_word("今天有两个会议。") -> Some("今天")
+// likely_word("研究生命很重要。") -> Some("研究"), since 研究 生命 is as long as 研究生 命
+fn likely_word<'a>(text: &'a str, overrides: &'a Overrides) -> Option<&'a str> {
+    let longest = |text: &str| {
+        prefix_words(text, overrides)
+            .last()
+            .map_or(0, |word| word.chars().count())
+    };
+    prefix_words(text, overrides)
+        .into_iter()
+        .map(|word| {
+            let len = word.chars().count() + longest(&text[word.len()..]);
+            (word, len, haoxue_dict::DICTIONARY.frequency(word))
+        })
+        .max_by(|(_, a_len, a_freq), (_, b_len, b_freq)| {
+            a_len.cmp(b_len).then(a_freq.total_cmp(b_freq))
+        })
+        .map(|(word, _, _)| word)
+}
+
+// Readings of a word, the most likely first. An override is the only reading. Otherwise the
+// dictionary's readings are used, with readings of proper nouns last.
+//
+// readings("行", &Overrides::default()) -> ["xíng", "háng", ...]
+fn readings(word: &str, overrides: &Overrides) -> Vec<String> {
+    if let Some(reading) = overrides.readings.get(word) {
+        return vec![reading.clone()];
     }
+    haoxue_dict::DICTIONARY
+        .lookup_entries(word)
+        .filter(|e| e.simplified() == word)
+        .map(|e| prettify_pinyin::prettify(e.pinyin()))
+        .sorted_by_key(|reading| reading.starts_with(char::is_uppercase))
+        .unique_by(|reading| reading.to_lowercase())
+        .collect()
+}
+
+// Position in the pinyin as written of a position in the pinyin without apostrophes.
+fn original_position(orig_pinyin: &str, position: usize) -> usize {
+    orig_pinyin
+        .char_indices()
+        .filter(|&(_, c)| c != '\'')
+        .nth(position)
+        .map_or(orig_pinyin.chars().count(), |(n, _)| {
+            orig_pinyin[..n].chars().count()
+        })
 }
 
 fn strip_prefix_no_tones<'a>(mut input: &'a str, mut prefix: &str) -> Option<&'a str> {
@@ -171,7 +653,7 @@ fn strip_prefix_no_tones<'a>(mut input: &'a str, mut prefix: &str) -> Option<&'a
     Some(input)
 }
 
-fn strip_tone(c: char) -> char {
+pub fn strip_tone(c: char) -> char {
     let tones = [
         ['ā', 'á', 'ǎ', 'à', 'a'],
         ['ē', 'é', 'ě', 'è', 'e'],
@@ -276,4 +758,143 @@ mod tests {
     fn basic_segment_4() {
         dbg!(Segment::join("他也不知道答案。", "Tā yě bù zhīdào dá'àn."));
     }
+
+    #[test]
+    fn alignment_error() {
+        let Err(ConvertErrorKind::Alignment(misalignment)) = Segment::join_with(
+            "我一定去。",
+            "Wǒ yǐdìng qù.",
+            true,
+            false,
+            &Overrides::default(),
+        ) else {
+            panic!("expected an alignment error");
+        };
+        assert_eq!(misalignment.chinese_position, 1);
+        assert_eq!(misalignment.pinyin_position, 3);
+        assert!(misalignment.candidates.contains(&Candidate {
+            word: "一定".to_string(),
+            pinyin: "yī dìng".to_string(),
+        }));
+    }
+
+    #[test]
+    fn sandhi_and_override_readings() {
+        let segments = Segment::join("我一定去。", "Wǒ yídìng qù.");
+        assert_eq!(segments[1].pinyin, "yī dìng");
+        let overrides: Overrides = serde_yaml::from_str("readings:\n  因素: yīn shù").unwrap();
+        let segments =
+            Segment::join_with("因素很多。", "Yīnshù hěn duō.", true, false, &overrides).unwrap();
+        assert_eq!(segments[0].pinyin, "yīn shù");
+    }
+
+    #[test]
+    fn alternate_readings() {
+        // 东西 is "thing" as dōng xi and "east and west" as dōng xī.
+        let segments = Segment::join("东西很多。", "Dōngxi hěn duō.");
+        assert_eq!(segments[0].readings().collect::<Vec<_>>(), vec!["dōng xi"]);
+        // Both readings of 个 are written ge with the neutral tone.
+        let segments = Segment::join("一个人。", "Yí ge rén.");
+        assert_eq!(segments[1].readings().collect::<Vec<_>>(), vec!["gè", "gě"]);
+        assert!(segments[2].alternates.is_empty());
+        // Readings that change the meaning are only reported.
+        let (segments, ambiguities) = Segment::generate("我们走了。", &Overrides::default());
+        assert_eq!(segments[2].readings().collect::<Vec<_>>(), vec!["le"]);
+        assert_eq!(ambiguities[0].word, "了");
+    }
+
+    #[test]
+    fn pinyin_position_skips_apostrophes() {
+        assert_eq!(original_position("dá'àn le", 1), 1);
+        assert_eq!(original_position("dá'àn le", 2), 3);
+        assert_eq!(original_position("dá'àn le", 3), 4);
+        assert_eq!(original_position("dá'àn", 4), 5);
+    }
+
+    #[test]
+    fn sentence_file_keeps_going() {
+        let text = "\
+Chinese: 我是学生。
+Pinyin: Wǒ shì xuésheng.
+English: I am a student.
+
+Chinese: 我一定去。
+Pinyin: Wǒ yǐdìng qù.
+English: I will definitely go.
+
+Chinese: 你好。
+Pinyin: Nǐ hǎo.
+
+Chinese: 他也不知道答案。
+Pinyin: Tā yě bù zhīdào dá'àn.
+English: He doesn't know the answer either.
+";
+        let results =
+            SentenceFile::new(text, true, false, &Overrides::default()).collect::<Vec<_>>();
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(ConvertError {
+                line: 5,
+                kind: ConvertErrorKind::Alignment(_)
+            })
+        ));
+        assert!(matches!(
+            results[2],
+            Err(ConvertError {
+                line: 9,
+                kind: ConvertErrorKind::Malformed { .. }
+            })
+        ));
+        assert_eq!(results[3].as_ref().unwrap().chinese(), "他也不知道答案。");
+    }
+
+    #[test]
+    fn generate_readings() {
+        let (segments, ambiguities) = Segment::generate("研究生命很重要。", &Overrides::default());
+        assert_eq!(
+            segments
+                .iter()
+                .map(|s| s.chinese.as_str())
+                .collect::<Vec<_>>(),
+            vec!["研究", "生命", "很", "重要", "。"]
+        );
+        assert_eq!(segments[3].pinyin, "zhòng yào");
+        assert_eq!(segments[4].pinyin, "");
+        assert!(ambiguities.is_empty());
+
+        let (_, ambiguities) = Segment::generate("他在银行。", &Overrides::default());
+        assert!(ambiguities.is_empty());
+        let (segments, ambiguities) = Segment::generate("我们走了。", &Overrides::default());
+        assert_eq!(segments[2].chinese, "了");
+        assert_eq!(ambiguities.len(), 1);
+        assert_eq!(ambiguities[0].word, "了");
+        assert_eq!(ambiguities[0].readings[0], segments[2].pinyin);
+    }
+
+    #[test]
+    fn sentence_file_without_pinyin() {
+        let text = "Chinese: 我们走了。\nEnglish: We left.\n\nChinese: 你好。\nPinyin: Nǐ hǎo.\nEnglish: Hello.";
+        let overrides = Overrides::default();
+        let mut file = SentenceFile::new(text, true, false, &overrides);
+        let exercises = file.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(exercises.len(), 2);
+        assert_eq!(exercises[0].chinese(), "我们走了。");
+        // Converted exercises are identified by their Chinese text.
+        assert!(exercises[0].id.is_empty());
+        assert_eq!(exercises[0].id(), "我们走了。");
+        let ambiguities = file.take_ambiguities();
+        assert_eq!(ambiguities.len(), 1);
+        assert_eq!(ambiguities[0].0, 1);
+    }
+
+    #[test]
+    fn sentence_file_without_blank_lines() {
+        let text = "Chinese: 你好。\nPinyin: Nǐ hǎo.\nEnglish: Hello.\nChinese: 我是学生。\nPinyin: Wǒ shì xuésheng.\nEnglish: I am a student.";
+        let exercises = SentenceFile::new(text, true, false, &Overrides::default())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(exercises.len(), 2);
+    }
 }
@@ -0,0 +1,206 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::convert::{ConvertError, ConvertErrorKind, Segment, SentenceFile};
+use crate::readings::Overrides;
+
+/// Walk through the sentences of a sentence file that fail to convert and let the user repair the
+/// pinyin of each. Each repaired sentence is written back to the file right away, with the file's
+/// own line endings. Prompts go to stderr, since stdout carries the converted exercises.
+pub fn fix_sentence_file<P: AsRef<Path>>(
+    path: P,
+    strict_segmentation: bool,
+    lax_pinyin: bool,
+    overrides: &Overrides,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(&path)?;
+    let mut lines = text.lines().map(str::to_string).collect::<Vec<_>>();
+    let newline = line_ending(&text);
+    let errors = SentenceFile::new(&text, strict_segmentation, lax_pinyin, overrides)
+        .filter_map(Result::err)
+        // Entries with missing lines are reported by the conversion itself.
+        .filter(|error| !matches!(error.kind, ConvertErrorKind::Malformed { .. }))
+        .collect::<Vec<_>>();
+
+    let total = errors.len();
+    let mut fixed = 0;
+    for (nth, error) in errors.into_iter().enumerate() {
+        eprintln!("[{}/{}]", nth + 1, total);
+        let Some(pinyin) = fix_sentence(error.clone(), strict_segmentation, lax_pinyin, overrides)?
+        else {
+            continue;
+        };
+        let pinyin_line = (error.line - 1..error.line + 2)
+            .find(|&n| {
+                lines
+                    .get(n)
+                    .is_some_and(|line| line.trim_start().starts_with("Pinyin:"))
+            })
+            .ok_or("Pinyin line not found")?;
+        lines[pinyin_line] = format!("Pinyin: {pinyin}");
+        std::fs::write(&path, lines.join(newline) + newline)?;
+        fixed += 1;
+    }
+
+    if fixed > 0 {
+        eprintln!("Fixed {fixed} sentences.");
+    }
+    Ok(())
+}
+
+// The line ending used by a file, "\r\n" or "\n".
+fn line_ending(text: &str) -> &'static str {
+    if text.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+// Ask the user how to repair the pinyin of a sentence until it converts. Returns the repaired
+// pinyin, or None if the sentence was skipped or can't be repaired by editing the pinyin.
+fn fix_sentence(
+    mut error: ConvertError,
+    strict_segmentation: bool,
+    lax_pinyin: bool,
+    overrides: &Overrides,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    loop {
+        eprintln!("{error}");
+        let misalignment = match &error.kind {
+            ConvertErrorKind::Alignment(m) | ConvertErrorKind::Segmentation(m) => m.clone(),
+            ConvertErrorKind::Malformed { .. } => return Ok(None),
+        };
+        for (nth, candidate) in misalignment.candidates.iter().enumerate() {
+            eprintln!("  {}: {} {}", nth + 1, candidate.word, candidate.pinyin);
+        }
+        eprintln!(
+            "Use reading N, sN to split the pinyin after reading N, e to edit, or k to skip."
+        );
+        let answer = prompt("> ")?;
+        let answer = answer.trim();
+
+        let reading = |n: &str| {
+            n.parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|n| misalignment.candidates.get(n))
+                .map(|candidate| candidate.pinyin.as_str())
+        };
+        let pinyin = if answer == "k" || answer.is_empty() {
+            return Ok(None);
+        } else if answer == "e" {
+            eprintln!("Pinyin: {}", misalignment.pinyin);
+            prompt("Pinyin: ")?.trim().to_string()
+        } else if let Some(reading) = answer.strip_prefix('s').and_then(reading) {
+            split_after_reading(&misalignment.pinyin, misalignment.pinyin_position, reading)
+        } else if let Some(reading) = reading(answer) {
+            replace_reading(&misalignment.pinyin, misalignment.pinyin_position, reading)
+        } else {
+            continue;
+        };
+
+        match Segment::join_with(
+            &misalignment.chinese,
+            &pinyin,
+            strict_segmentation,
+            lax_pinyin,
+            overrides,
+        ) {
+            Ok(_) => return Ok(Some(pinyin)),
+            Err(kind) => error.kind = kind,
+        }
+    }
+}
+
+fn prompt(text: &str) -> io::Result<String> {
+    eprint!("{text}");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line)
+}
+
+// Number of characters of `pinyin`, starting at `position`, that spell as many letters as the
+// reading has. Spaces and apostrophes within the word are included.
+fn reading_len(pinyin: &str, position: usize, reading: &str) -> usize {
+    let letters = reading.chars().filter(|c| c.is_alphabetic()).count();
+    let mut seen = 0;
+    pinyin
+        .chars()
+        .skip(position)
+        .take_while(|c| {
+            if seen == letters {
+                return false;
+            }
+            if c.is_alphabetic() {
+                seen += 1;
+            }
+            true
+        })
+        .count()
+}
+
+// Replace the syllables at `position` with a dictionary reading. The reading is written as one
+// word and keeps the capitalization of the pinyin it replaces.
+//
+// replace_reading("Wǒ yídìng qù.", 3, "yī dìng") -> "Wǒ yīdìng qù."
+// replace_reading("Yídìng.", 0, "yī dìng") -> "Yīdìng."
+fn replace_reading(pinyin: &str, position: usize, reading: &str) -> String {
+    let len = reading_len(pinyin, position, reading);
+    let mut replacement = reading.replace(' ', "");
+    if pinyin.chars().nth(position).is_some_and(char::is_uppercase) {
+        let mut chars = replacement.chars();
+        replacement = chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+    }
+    pinyin.chars().take(position).collect::<String>()
+        + &replacement
+        + &pinyin.chars().skip(position + len).collect::<String>()
+}
+
+// Split the pinyin word at `position` after the syllables of a dictionary reading.
+//
+// split_after_reading("Nǐhǎo.", 0, "nǐ") -> "Nǐ hǎo."
+fn split_after_reading(pinyin: &str, position: usize, reading: &str) -> String {
+    let at = position + reading_len(pinyin, position, reading);
+    pinyin.chars().take(at).collect::<String>() + " " + &pinyin.chars().skip(at).collect::<String>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_reading() {
+        assert_eq!(
+            replace_reading("Wǒ yídìng qù.", 3, "yī dìng"),
+            "Wǒ yīdìng qù."
+        );
+        assert_eq!(replace_reading("Yídìng.", 0, "yī dìng"), "Yīdìng.");
+        assert_eq!(
+            replace_reading("Tā búduàn nǔlì.", 3, "bù duàn"),
+            "Tā bùduàn nǔlì."
+        );
+        assert_eq!(replace_reading("yí dìng", 0, "yī dìng"), "yīdìng");
+        assert_eq!(replace_reading("sān ge rén", 4, "gè"), "sān gè rén");
+    }
+
+    #[test]
+    fn test_line_ending() {
+        assert_eq!(line_ending("Chinese: 你好\r\nPinyin: nǐhǎo\r\n"), "\r\n");
+        assert_eq!(line_ending("Chinese: 你好\nPinyin: nǐhǎo\n"), "\n");
+        assert_eq!(line_ending(""), "\n");
+    }
+
+    #[test]
+    fn test_split_after_reading() {
+        assert_eq!(split_after_reading("Nǐhǎo.", 0, "nǐ"), "Nǐ hǎo.");
+        assert_eq!(
+            split_after_reading("wǒ dá'ànshì", 3, "dá àn"),
+            "wǒ dá'àn shì"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use haoxue_dict::DICTIONARY;
+use itertools::Itertools;
+
+use crate::convert::{strip_tone, Exercise};
+
+// Candidates shown at once. They are picked with the keys 1-9.
+pub const MAX_CANDIDATES: usize = 9;
+
+// Pinyin input method. Maps toneless pinyin to the words that can be written with it, like the
+// candidate list of an IME.
+//
+// The dictionary can only be searched by Chinese text, so the candidates are the words of the
+// exercises, every character they are written with, and every common character of the dictionary,
+// looked up for all of their readings.
+pub struct Ime {
+    // "xuesheng" -> ["学生"]
+    words: BTreeMap<String, BTreeSet<String>>,
+    // Words and characters of the exercises.
+    corpus: HashSet<String>,
+}
+
+// Characters looked up in the dictionary besides the exercises': the CJK Unified Ideographs block,
+// which holds the characters in common use.
+const DICTIONARY_CHARACTERS: std::ops::RangeInclusive<char> = '\u{4e00}'..='\u{9fff}';
+
+impl Ime {
+    pub fn new(exercises: &[Exercise]) -> Self {
+        let mut words: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let corpus = exercises
+            .iter()
+            .flat_map(|e| e.segments.iter())
+            .filter(|s| !s.pinyin.is_empty())
+            .flat_map(|s| {
+                s.chinese
+                    .chars()
+                    .map(String::from)
+                    .chain(std::iter::once(s.chinese.clone()))
+            })
+            .collect::<HashSet<_>>();
+        let texts = corpus
+            .iter()
+            .cloned()
+            .chain(DICTIONARY_CHARACTERS.map(String::from))
+            .unique();
+        for text in texts {
+            for entry in DICTIONARY
+                .lookup_entries(&text)
+                .filter(|e| e.simplified() == text)
+            {
+                words
+                    .entry(input_key(entry.pinyin()))
+                    .or_default()
+                    .insert(text.clone());
+            }
+        }
+        Ime { words, corpus }
+    }
+
+    // Words matching the typed pinyin, best first. Exact matches come before words that merely
+    // start with the input, words of the exercises before other characters of the dictionary, and
+    // more frequent words before rarer ones.
+    pub fn candidates(&self, input: &str) -> Vec<&str> {
+        let key = input_key(input);
+        if key.is_empty() {
+            return vec![];
+        }
+        let key = key.as_str();
+        self.words
+            .range(key.to_string()..)
+            .take_while(|(pinyin, _)| pinyin.starts_with(key))
+            .flat_map(|(pinyin, words)| words.iter().map(move |w| (pinyin != key, w.as_str())))
+            .sorted_by(|(a_partial, a), (b_partial, b)| {
+                a_partial
+                    .cmp(b_partial)
+                    .then(self.corpus.contains(*b).cmp(&self.corpus.contains(*a)))
+                    .then(DICTIONARY.frequency(b).total_cmp(&DICTIONARY.frequency(a)))
+            })
+            .map(|(_, word)| word)
+            .unique()
+            .take(MAX_CANDIDATES)
+            .collect()
+    }
+}
+
+// Normalize pinyin the way it is typed into an IME: lowercase, no tones, no separators, and 'v'
+// for 'ü'.
+//
+// input_key("xue2 sheng5") -> "xuesheng"
+// input_key("Lǜsè") -> "lvse"
+// input_key("lu:4") -> "lv"
+fn input_key(pinyin: &str) -> String {
+    pinyin
+        .to_lowercase()
+        .replace("u:", "v")
+        .chars()
+        .map(strip_tone)
+        .map(|c| if c == 'ü' { 'v' } else { c })
+        .filter(|c| c.is_ascii_alphabetic())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_key() {
+        assert_eq!(input_key("xue2 sheng5"), "xuesheng");
+        assert_eq!(input_key("xué sheng"), "xuesheng");
+        assert_eq!(input_key("Lǜsè"), "lvse");
+        assert_eq!(input_key("lu:4"), "lv");
+        assert_eq!(input_key("dá'àn"), "daan");
+    }
+
+    #[test]
+    fn test_candidates() {
+        let ime = Ime::new(&[Exercise::from_segments(&[
+            ("我", "wǒ"),
+            ("是", "shì"),
+            ("学生", "xué sheng"),
+            ("。", ""),
+        ])]);
+        assert_eq!(ime.candidates("xuesheng"), vec!["学生"]);
+        assert_eq!(ime.candidates("xué")[0], "学");
+        assert_eq!(ime.candidates("wo")[0], "我");
+        assert!(ime.candidates("sheng").contains(&"生"));
+        // Characters of the dictionary are candidates too, after the words of the exercises.
+        assert_eq!(ime.candidates("shi")[0], "是");
+        assert!(ime.candidates("xue").contains(&"雪"));
+        assert!(ime.candidates("ni").contains(&"你"));
+        let ime = Ime::new(&[Exercise::from_segments(&[("诗", "shī")])]);
+        assert_eq!(ime.candidates("shi")[0], "诗");
+        assert!(ime.candidates("shi").contains(&"是"));
+        assert_eq!(ime.candidates(""), Vec::<&str>::new());
+        assert_eq!(ime.candidates("。"), Vec::<&str>::new());
+    }
+}
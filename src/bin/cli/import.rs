@@ -0,0 +1,381 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::convert::{ConvertError, ConvertErrorKind, Row};
+
+/// Formats sentences can be converted from.
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum Format {
+    // Entries of Chinese:, Pinyin: and English: lines.
+    #[default]
+    Sentences,
+    // Tab-separated columns. Also reads Anki's "Notes in Plain Text" exports.
+    Tsv,
+    Csv,
+    // Anki deck package (.apkg).
+    Anki,
+}
+
+impl Format {
+    /// Guess the format of a file from its extension.
+    pub fn guess<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("tsv") => Format::Tsv,
+            Some("csv") => Format::Csv,
+            Some("apkg") => Format::Anki,
+            _ => Format::Sentences,
+        }
+    }
+}
+
+/// Which column of a table, or field of an Anki note, holds each part of a sentence. Written as
+/// the part of each column in order, with `-` for columns to ignore: `-,chinese,english,pinyin`.
+/// The pinyin column is optional.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Columns {
+    pub chinese: usize,
+    pub pinyin: Option<usize>,
+    pub english: usize,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            chinese: 0,
+            pinyin: Some(1),
+            english: 2,
+        }
+    }
+}
+
+impl FromStr for Columns {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut chinese, mut pinyin, mut english) = (None, None, None);
+        for (n, name) in s.split(',').map(str::trim).enumerate() {
+            let column = match name.to_lowercase().as_str() {
+                "chinese" => &mut chinese,
+                "pinyin" => &mut pinyin,
+                "english" => &mut english,
+                "-" => continue,
+                _ => return Err(format!("unknown column: {name}")),
+            };
+            if column.replace(n).is_some() {
+                return Err(format!("column given twice: {name}"));
+            }
+        }
+        match (chinese, english) {
+            (Some(chinese), Some(english)) => Ok(Columns {
+                chinese,
+                pinyin,
+                english,
+            }),
+            _ => Err("both a chinese and an english column are needed".to_string()),
+        }
+    }
+}
+
+impl Columns {
+    // The sentence in the fields of a row.
+    fn row(&self, line: usize, fields: &[String]) -> Result<Row, ConvertError> {
+        let field = |n: usize| fields.get(n).cloned();
+        let (Some(chinese), Some(english)) = (field(self.chinese), field(self.english)) else {
+            return Err(ConvertError {
+                line,
+                kind: ConvertErrorKind::Malformed {
+                    text: fields.join(" | "),
+                },
+            });
+        };
+        Ok(Row {
+            line,
+            chinese,
+            pinyin: self
+                .pinyin
+                .and_then(field)
+                .filter(|pinyin| !pinyin.is_empty()),
+            english,
+        })
+    }
+
+    // Whether the first row of a table names the columns instead of holding a sentence.
+    fn is_header(&self, fields: &[String]) -> bool {
+        fields
+            .get(self.chinese)
+            .is_some_and(|field| field.trim().eq_ignore_ascii_case("chinese"))
+    }
+}
+
+/// Read the sentences of a file in a format other than a sentence file. Rows are numbered from 1
+/// by line for tables, and by note for Anki decks.
+pub fn read_rows<P: AsRef<Path>>(
+    path: P,
+    format: Format,
+    columns: &Columns,
+) -> Result<Vec<Result<Row, ConvertError>>, Box<dyn std::error::Error>> {
+    let table = |delimiter| -> Result<_, Box<dyn std::error::Error>> {
+        Ok(read_table(&fs::read_to_string(&path)?, delimiter, columns))
+    };
+    match format {
+        Format::Sentences => Err("sentence files are not read as rows".into()),
+        Format::Tsv => table(b'\t'),
+        Format::Csv => table(b','),
+        Format::Anki => read_anki_package(&path, columns),
+    }
+}
+
+// Rows of a table with the given column delimiter. Lines starting with '#' are skipped, like the
+// headers of Anki's text exports. Fields are HTML only when such a header says so.
+fn read_table(text: &str, delimiter: u8, columns: &Columns) -> Vec<Result<Row, ConvertError>> {
+    let html = text
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .any(|line| line.trim() == "#html:true");
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_reader(text.as_bytes());
+    reader
+        .records()
+        .enumerate()
+        .filter_map(|(n, record)| {
+            let record = match record {
+                Ok(record) => record,
+                Err(err) => {
+                    let line = err.position().map_or(0, |pos| pos.line() as usize);
+                    return Some(Err(ConvertError {
+                        line,
+                        kind: ConvertErrorKind::Malformed {
+                            text: err.to_string(),
+                        },
+                    }));
+                }
+            };
+            let line = record.position().map_or(0, |pos| pos.line() as usize);
+            let fields = record
+                .iter()
+                .map(|field| {
+                    if html {
+                        strip_html(field)
+                    } else {
+                        field.to_string()
+                    }
+                })
+                .collect::<Vec<_>>();
+            if n == 0 && columns.is_header(&fields) {
+                return None;
+            }
+            Some(columns.row(line, &fields))
+        })
+        .collect()
+}
+
+// Notes of an Anki deck package. The package is a zip archive holding the deck's SQLite database,
+// whose notes keep their fields separated by 0x1f.
+fn read_anki_package<P: AsRef<Path>>(
+    path: P,
+    columns: &Columns,
+) -> Result<Vec<Result<Row, ConvertError>>, Box<dyn std::error::Error>> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    // Packages only meant for recent Anki versions hold a compressed database, next to an old
+    // style one asking to upgrade Anki.
+    if archive.by_name("collection.anki21b").is_ok() {
+        return Err(
+            "unsupported deck package: export it with \"Support older Anki versions\"".into(),
+        );
+    }
+    let name = if archive.file_names().any(|name| name == "collection.anki21") {
+        "collection.anki21"
+    } else {
+        "collection.anki2"
+    };
+    let mut collection = archive.by_name(name)?;
+    let mut database = tempfile::NamedTempFile::new()?;
+    io::copy(&mut collection, &mut database)?;
+
+    let connection = rusqlite::Connection::open(database.path())?;
+    let mut statement = connection.prepare("SELECT flds FROM notes ORDER BY id")?;
+    let notes = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(notes
+        .iter()
+        .enumerate()
+        .map(|(n, note)| {
+            let fields = note.split('\x1f').map(strip_html).collect::<Vec<_>>();
+            columns.row(n + 1, &fields)
+        })
+        .collect())
+}
+
+// Anki fields are HTML, and play sounds with [sound:file] tags. Keep only their text.
+//
+// strip_html("<b>你好</b>&nbsp;<br>[sound:nihao.mp3]") -> "你好"
+fn strip_html(field: &str) -> String {
+    let mut text = String::new();
+    let mut tag: Option<String> = None;
+    for c in field.chars() {
+        match (&mut tag, c) {
+            (None, '<') => tag = Some(String::new()),
+            (Some(name), '>') => {
+                // Line breaks separate words.
+                let name = name.trim_matches('/').to_lowercase();
+                if ["br", "div", "p"].contains(&name.split_whitespace().next().unwrap_or("")) {
+                    text.push(' ');
+                }
+                tag = None;
+            }
+            (Some(name), c) => name.push(c),
+            (None, c) => text.push(c),
+        }
+    }
+    let mut text = decode_entities(&text);
+    while let Some(start) = text.find("[sound:") {
+        let Some(end) = text[start..].find(']') else {
+            break;
+        };
+        text.replace_range(start..start + end + 1, " ");
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Replace HTML entities, named or numeric, with their characters. Anything else starting with '&'
+// is kept as written.
+//
+// decode_entities("R&amp;D &#20320;&#x597D;") -> "R&D 你好"
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .map(|end| &rest[1..end + 1])
+            .filter(|entity| {
+                entity
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '#')
+            });
+        let c = entity.and_then(|entity| match entity {
+            "nbsp" => Some(' '),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "amp" => Some('&'),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns() {
+        assert_eq!(
+            "chinese,pinyin,english".parse::<Columns>(),
+            Ok(Columns::default())
+        );
+        assert_eq!(
+            "-,english,chinese".parse::<Columns>(),
+            Ok(Columns {
+                chinese: 2,
+                pinyin: None,
+                english: 1,
+            })
+        );
+        assert!("chinese,pinyin".parse::<Columns>().is_err());
+        assert!("chinese,chinese,english".parse::<Columns>().is_err());
+        assert!("chinese,french,english".parse::<Columns>().is_err());
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(strip_html("<b>你好</b>&nbsp;<br>"), "你好");
+        assert_eq!(strip_html("Tom &amp; Jerry<br/>run."), "Tom & Jerry run.");
+        assert_eq!(strip_html("我是<b>学生</b>。"), "我是学生。");
+        assert_eq!(strip_html("&#20320;&#x597D;。[sound:nihao.mp3]"), "你好。");
+        assert_eq!(strip_html("[sound:a.mp3]Hello[sound:b.mp3]"), "Hello");
+        assert_eq!(strip_html("R&D &amp;c &#xZZ;"), "R&D &c &#xZZ;");
+    }
+
+    #[test]
+    fn test_read_table() {
+        let text = "\
+#separator:tab
+#html:true
+Chinese\tPinyin\tEnglish
+你好。\tNǐ hǎo.\tHello.
+我们走了。\t\tWe left.
+再见。
+";
+        let rows = read_table(text, b'\t', &Columns::default());
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            Ok(Row {
+                line: 4,
+                chinese: "你好。".to_string(),
+                pinyin: Some("Nǐ hǎo.".to_string()),
+                english: "Hello.".to_string(),
+            })
+        );
+        assert_eq!(rows[1].as_ref().unwrap().pinyin, None);
+        assert!(matches!(
+            rows[2],
+            Err(ConvertError {
+                line: 6,
+                kind: ConvertErrorKind::Malformed { .. }
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_csv_columns() {
+        let text = "1,\"Hello, world.\",你好，世界。\n";
+        let columns = "-,english,chinese".parse().unwrap();
+        let rows = read_table(text, b',', &columns);
+        assert_eq!(rows[0].as_ref().unwrap().english, "Hello, world.");
+        assert_eq!(rows[0].as_ref().unwrap().chinese, "你好，世界。");
+    }
+
+    #[test]
+    fn test_read_table_keeps_text() {
+        let text = "我们<b>走</b>了。\t\tR&D&nbsp;a<b\n";
+        let rows = read_table(text, b'\t', &Columns::default());
+        assert_eq!(rows[0].as_ref().unwrap().chinese, "我们<b>走</b>了。");
+        assert_eq!(rows[0].as_ref().unwrap().english, "R&D&nbsp;a<b");
+
+        // Anki's text exports say when their fields are HTML.
+        let text = format!("#html:true\n{text}");
+        let rows = read_table(&text, b'\t', &Columns::default());
+        assert_eq!(rows[0].as_ref().unwrap().chinese, "我们走了。");
+    }
+}
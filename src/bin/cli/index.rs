@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::convert::Exercise;
+
+/// Exercises and the word list, indexed for the lookups done when picking exercises. Built once
+/// when they are loaded. Exercises are referred to by their position.
+pub struct ExerciseIndex {
+    exercises: Vec<Exercise>,
+    word_list: Vec<String>,
+    // Exercise::words of each exercise.
+    words: Vec<Vec<String>>,
+    // Word -> positions of the exercises it occurs in, in order.
+    by_word: HashMap<String, Vec<usize>>,
+    in_list: HashSet<String>,
+}
+
+impl ExerciseIndex {
+    pub fn new(exercises: Vec<Exercise>, word_list: Vec<String>) -> Self {
+        let words = exercises
+            .iter()
+            .map(|exercise| exercise.words().into_iter().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut by_word: HashMap<String, Vec<usize>> = HashMap::new();
+        for (n, words) in words.iter().enumerate() {
+            for word in words {
+                let positions = by_word.entry(word.clone()).or_default();
+                if positions.last() != Some(&n) {
+                    positions.push(n);
+                }
+            }
+        }
+        let in_list = word_list.iter().cloned().collect();
+        ExerciseIndex {
+            exercises,
+            word_list,
+            words,
+            by_word,
+            in_list,
+        }
+    }
+
+    pub fn exercises(&self) -> &[Exercise] {
+        &self.exercises
+    }
+
+    pub fn word_list(&self) -> &[String] {
+        &self.word_list
+    }
+
+    /// The words of an exercise, as listed by Exercise::words.
+    pub fn words(&self, exercise: usize) -> &[String] {
+        &self.words[exercise]
+    }
+
+    /// Positions of the exercises a word occurs in.
+    pub fn with_word(&self, word: &str) -> &[usize] {
+        self.by_word.get(word).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn in_list(&self, word: &str) -> bool {
+        self.in_list.contains(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        let exercises: Vec<Exercise> = serde_yaml::from_str(
+            r#"
+            - segments:
+              - {chinese: 我, pinyin: wǒ}
+              - {chinese: 是, pinyin: shì}
+              - {chinese: 我, pinyin: wǒ}
+              english: It's me.
+            - segments:
+              - {chinese: 你, pinyin: nǐ}
+              - {chinese: 是, pinyin: shì}
+              english: You are.
+            "#,
+        )
+        .unwrap();
+        let index = ExerciseIndex::new(exercises, vec!["是".to_string()]);
+        assert_eq!(index.with_word("我"), &[0]);
+        assert_eq!(index.with_word("是"), &[0, 1]);
+        assert_eq!(index.with_word("他"), &[] as &[usize]);
+        assert_eq!(index.words(1), &["你", "是"]);
+        assert!(index.in_list("是"));
+        assert!(!index.in_list("我"));
+    }
+
+    // Picking exercises with and without the index, on a corpus the size of a full sentence
+    // collection. Run with `cargo test --release -- --ignored --nocapture bench`.
+    #[test]
+    #[ignore]
+    fn bench_next_exercise() {
+        use crate::model::UserModel;
+        use chrono::Utc;
+        use rand::{rngs::SmallRng, Rng, SeedableRng};
+        use std::time::Instant;
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let vocabulary = (0..20_000).map(|n| format!("w{n}")).collect::<Vec<_>>();
+        let exercises = (0..50_000)
+            .map(|_| {
+                let words = (0..rng.gen_range(4..12))
+                    .map(|_| {
+                        // Common words are more likely, like in real sentences.
+                        vocabulary[rng.gen_range(0..vocabulary.len()) * rng.gen_range(1..=10) / 10]
+                            .as_str()
+                    })
+                    .collect::<Vec<_>>();
+                Exercise::from_words(&words)
+            })
+            .collect::<Vec<_>>();
+        let word_list = vocabulary[..5_000].to_vec();
+        let targets = &vocabulary[..200];
+        let model = UserModel::new();
+        let now = Utc::now();
+
+        // The lookup as done before the index: scan every exercise for the target word and the
+        // word list for every word.
+        let start = Instant::now();
+        let scanned = targets
+            .iter()
+            .map(|target| {
+                exercises
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, exercise)| exercise.words().contains(&target))
+                    .min_by_key(|(_, exercise)| {
+                        let words = exercise.words();
+                        let in_list = words.iter().filter(|w| word_list.contains(w)).count();
+                        (words.len() - in_list, in_list)
+                    })
+                    .map(|(n, _)| n)
+            })
+            .collect::<Vec<_>>();
+        let scan_time = start.elapsed();
+
+        let start = Instant::now();
+        let index = ExerciseIndex::new(exercises, word_list);
+        let build_time = start.elapsed();
+        let start = Instant::now();
+        let indexed = targets
+            .iter()
+            .map(|target| model.next_exercise(now, &index, target))
+            .collect::<Vec<_>>();
+        let index_time = start.elapsed();
+
+        assert_eq!(scanned, indexed);
+        println!(
+            "{} lookups: scan {scan_time:?}, index {index_time:?} (built in {build_time:?})",
+            targets.len()
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::convert::Exercise;
+use crate::readings::Overrides;
+
+/// Problems exercises can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    // The pinyin of a word matches none of its dictionary readings.
+    UnknownReading,
+    // The word isn't in the dictionary, so its pinyin can't be checked.
+    UnknownWord,
+    // Punctuation has pinyin.
+    PunctuationPinyin,
+    MissingEnglish,
+    // Another exercise has the same id or the same Chinese text.
+    Duplicate,
+    // A word occurs more than once, and Exercise::words lists it more than once.
+    RepeatedWord,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Check {
+    pub fn severity(self) -> Severity {
+        match self {
+            Check::UnknownWord | Check::RepeatedWord => Severity::Warning,
+            Check::UnknownReading
+            | Check::PunctuationPinyin
+            | Check::MissingEnglish
+            | Check::Duplicate => Severity::Error,
+        }
+    }
+}
+
+// Checks and severities are shown by the names they are serialized with.
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.serialize(f)
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.serialize(f)
+    }
+}
+
+/// A problem found in an exercise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    pub file: String,
+    // Position of the exercise in its file, counting from 1.
+    pub exercise: usize,
+    pub id: String,
+    pub severity: Severity,
+    pub check: Check,
+    pub message: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} {}: {}[{}]: {}",
+            self.file, self.exercise, self.id, self.severity, self.check, self.message
+        )
+    }
+}
+
+/// Check the exercises of each file, given as file name and exercises. Problems are returned in
+/// the order of the exercises.
+pub fn lint(files: &[(String, Vec<Exercise>)], overrides: &Overrides) -> Vec<Problem> {
+    let mut problems = vec![];
+    // Exercise id, and Chinese text -> where it first occurs.
+    let mut first_id: HashMap<String, (&str, usize)> = HashMap::new();
+    let mut first_chinese: HashMap<String, (&str, usize)> = HashMap::new();
+    for (file, exercises) in files {
+        for (n, exercise) in exercises.iter().enumerate() {
+            let id = exercise.id();
+            let mut found = lint_exercise(exercise, overrides);
+            let same_id = first_id.get(&id).map(|first| ("id", first));
+            let same_chinese = first_chinese
+                .get(&exercise.chinese())
+                .map(|first| ("sentence", first));
+            if let Some((what, (first_file, first_n))) = same_id.or(same_chinese) {
+                found.push((
+                    Check::Duplicate,
+                    format!("same {what} as {first_file}:{first_n}"),
+                ));
+            }
+            first_id.entry(id.clone()).or_insert((file, n + 1));
+            first_chinese
+                .entry(exercise.chinese())
+                .or_insert((file, n + 1));
+            problems.extend(found.into_iter().map(|(check, message)| Problem {
+                file: file.clone(),
+                exercise: n + 1,
+                id: id.clone(),
+                severity: check.severity(),
+                check,
+                message,
+            }));
+        }
+    }
+    problems
+}
+
+// Problems of a single exercise.
+fn lint_exercise(exercise: &Exercise, overrides: &Overrides) -> Vec<(Check, String)> {
+    let mut found = vec![];
+    if exercise.english.trim().is_empty() {
+        found.push((Check::MissingEnglish, "no English translation".to_string()));
+    }
+    for segment in exercise.segments.iter().filter(|s| !s.pinyin.is_empty()) {
+        let word = segment.chinese.as_str();
+        if word.chars().all(|c| !c.is_alphanumeric()) {
+            found.push((
+                Check::PunctuationPinyin,
+                format!("{word} has pinyin {}", segment.pinyin),
+            ));
+            continue;
+        }
+        let readings = haoxue_dict::DICTIONARY
+            .lookup_entries(word)
+            .filter(|e| e.simplified() == word)
+            .map(|e| prettify_pinyin::prettify(e.pinyin()))
+            .chain(overrides.readings.get(word).cloned())
+            .unique()
+            .collect::<Vec<_>>();
+        if readings.is_empty() {
+            found.push((
+                Check::UnknownWord,
+                format!("{word} is not in the dictionary"),
+            ));
+            continue;
+        }
+        for reading in segment.readings() {
+            let pinyin = compact(reading);
+            let accepted = readings
+                .iter()
+                .flat_map(|reading| overrides.accepted(word, reading))
+                .any(|reading| compact(&reading) == pinyin);
+            if !accepted {
+                found.push((
+                    Check::UnknownReading,
+                    format!(
+                        "{word} is read {reading}, the dictionary has {}",
+                        readings.join(", ")
+                    ),
+                ));
+            }
+        }
+    }
+    for (word, count) in exercise.words().into_iter().counts() {
+        if count > 1 {
+            found.push((
+                Check::RepeatedWord,
+                format!("{word} is listed {count} times by Exercise::words"),
+            ));
+        }
+    }
+    found
+}
+
+// Pinyin compared case-insensitively and regardless of how syllables are separated.
+fn compact(pinyin: &str) -> String {
+    pinyin.to_lowercase().replace([' ', '\''], "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(segments: &[(&str, &str)], english: &str) -> Exercise {
+        Exercise {
+            english: english.to_string(),
+            ..Exercise::from_segments(segments)
+        }
+    }
+
+    fn checks(exercise: &Exercise) -> Vec<Check> {
+        lint_exercise(exercise, &Overrides::default())
+            .into_iter()
+            .map(|(check, _)| check)
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_exercise() {
+        let e = exercise(
+            &[("我", "wǒ"), ("一定", "yí dìng"), ("去", "qù"), ("。", "")],
+            "I will definitely go.",
+        );
+        assert_eq!(checks(&e), vec![]);
+    }
+
+    #[test]
+    fn test_exercise_checks() {
+        let e = exercise(&[("我", "wó"), ("。", "o")], " ");
+        assert_eq!(
+            checks(&e),
+            vec![
+                Check::MissingEnglish,
+                Check::UnknownReading,
+                Check::PunctuationPinyin
+            ]
+        );
+        let e = exercise(&[("我", "wǒ"), ("是", "shì"), ("我", "wǒ")], "Me, it's me.");
+        assert_eq!(checks(&e), vec![Check::RepeatedWord]);
+        let e = exercise(&[("喵喵喵", "miāo miāo miāo")], "Meow.");
+        assert_eq!(checks(&e), vec![Check::UnknownWord]);
+    }
+
+    #[test]
+    fn test_duplicates_across_files() {
+        let e = exercise(&[("我", "wǒ")], "Me.");
+        let files = vec![
+            ("a.yaml".to_string(), vec![e.clone()]),
+            (
+                "b.yaml".to_string(),
+                vec![exercise(&[("你", "nǐ")], "You."), e],
+            ),
+        ];
+        let problems = lint(&files, &Overrides::default());
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].check, Check::Duplicate);
+        assert_eq!(
+            (problems[0].file.as_str(), problems[0].exercise),
+            ("b.yaml", 2)
+        );
+        assert_eq!(
+            problems[0].to_string(),
+            "b.yaml:2 我: error[duplicate]: same id as a.yaml:1"
+        );
+
+        // Exercises with their own ids can still be the same sentence.
+        let with_id = |id: &str| Exercise {
+            id: id.to_string(),
+            ..exercise(&[("我", "wǒ")], "Me.")
+        };
+        let files = vec![("a.yaml".to_string(), vec![with_id("1"), with_id("2")])];
+        let problems = lint(&files, &Overrides::default());
+        assert_eq!(
+            problems.iter().map(Problem::to_string).collect::<Vec<_>>(),
+            vec!["a.yaml:2 2: error[duplicate]: same sentence as a.yaml:1"]
+        );
+    }
+}
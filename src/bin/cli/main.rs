@@ -6,32 +6,65 @@
 
 use std::path::PathBuf;
 
-use anes::*;
 use chrono::Utc;
 use clap::{Parser, Subcommand};
-use itertools::Either;
+use itertools::{Either, Itertools};
 use openai_dive::v1::api::Client;
 use openai_dive::v1::models::TTSEngine;
 use openai_dive::v1::resources::audio::{
     AudioSpeechParameters, AudioSpeechResponseFormat, AudioVoice,
 };
-use ordered_float::OrderedFloat;
-use rodio::{Decoder, OutputStream, Sink, Source};
+use rodio::{Decoder, OutputStream, Sink};
 
 use std::error::Error;
 
 use std::fs::File;
-use std::io::{BufReader, Read, Write};
+use std::io::{BufReader, Read};
 
 use haoxue_dict::Dictionary;
 
 mod convert;
-use convert::Exercise;
+use convert::{Exercise, SentenceFile};
 
 mod train;
-use train::train;
+use train::{train, Mode};
+
+mod ime;
 
 mod model;
+use model::{Grade, Skill};
+
+mod index;
+use index::ExerciseIndex;
+
+mod status;
+
+mod scheduler;
+use scheduler::SchedulerKind;
+
+mod review_log;
+use review_log::ReviewLog;
+
+mod profile;
+use profile::Profile;
+
+mod optimize;
+
+mod fix;
+
+mod story;
+use story::{Bookmarks, Story};
+
+mod readings;
+use readings::Overrides;
+
+mod import;
+use import::{Columns, Format};
+
+mod lint;
+use lint::Severity;
+
+mod tile;
 
 #[derive(Parser, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -48,6 +81,20 @@ enum Command {
         lax_segmentation: bool,
         #[arg(long)]
         strict_pinyin: bool,
+        // Convert the remaining sentences after a failure and report all failures at the end.
+        #[arg(long)]
+        keep_going: bool,
+        // Interactively repair the pinyin of sentences that fail to convert and write the repairs
+        // back to the sentence file before converting.
+        #[arg(long)]
+        fix: bool,
+        // Format of the sentence file. Guessed from the file extension by default.
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        // Part of the sentence in each column of a table or field of an Anki note, with '-' for
+        // columns to ignore.
+        #[arg(long, default_value = "chinese,pinyin,english")]
+        columns: Columns,
     },
     Sort {
         word_file: PathBuf,
@@ -57,10 +104,44 @@ enum Command {
         exercise_file: PathBuf,
         #[arg(long)]
         frequency_sort: bool,
+        #[arg(long, value_enum, default_value_t)]
+        scheduler: SchedulerKind,
+        #[arg(long, value_enum, default_value_t)]
+        mode: Mode,
+        // In cloze mode, also blank the other words due for review.
+        #[arg(long)]
+        blank_due: bool,
     },
     Audio {
         exercise_file: PathBuf,
     },
+    // Read an exercise file in order as one text, resuming where the last session stopped.
+    Story {
+        story_file: PathBuf,
+        // Hide the pinyin of words remembered for at least this many days.
+        #[arg(long, default_value_t = 7.0)]
+        known_days: f64,
+        // Start from the beginning instead of the bookmark.
+        #[arg(long)]
+        restart: bool,
+        // Listen to each sentence and type its pinyin before the text is revealed.
+        #[arg(long)]
+        audio: bool,
+        #[arg(long, value_enum, default_value_t)]
+        scheduler: SchedulerKind,
+    },
+    // Rebuild the user model from the review log.
+    Replay {
+        #[arg(long)]
+        exercise_files: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t)]
+        scheduler: SchedulerKind,
+        // Write the rebuilt model here instead of replacing the stored user model.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    // Fit the scheduler parameters to the review log and store them in the user's profile.
+    Optimize,
     Tile {
         word_file: PathBuf,
         #[arg(long)]
@@ -72,6 +153,30 @@ enum Command {
         output_format: OutputFormat,
         #[arg(long)]
         frequency_sort: bool,
+        // Report which words the course introduces too soon or needlessly, instead of printing
+        // the course.
+        #[arg(long)]
+        report: bool,
+        // Search for a course that introduces fewer words out of turn than picking the cheapest
+        // exercise for each word in turn. The costs of both courses are printed to stderr.
+        #[arg(long)]
+        optimize: bool,
+        // Seconds to spend searching with --optimize.
+        #[arg(long, default_value = "10", value_parser = parse_seconds)]
+        time_budget: std::time::Duration,
+        // Add exercises until every word is met in at least this many of them.
+        #[arg(long, default_value_t = 1)]
+        min_exposures: usize,
+        // Exercises between the first two exposures of a word. Each later gap is twice as long.
+        #[arg(long, default_value_t = 5)]
+        spacing: usize,
+    },
+    // Check exercise files for mistakes. Exits with an error status if any are found.
+    Lint {
+        #[arg(required = true)]
+        exercise_files: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t)]
+        output_format: OutputFormat,
     },
 }
 
@@ -79,8 +184,8 @@ enum Command {
 enum OutputFormat {
     #[default]
     Human,
-    CSV,
-    YAML,
+    Csv,
+    Yaml,
 }
 
 #[tokio::main]
@@ -92,19 +197,55 @@ async fn main() -> Result<(), Box<dyn Error>> {
             sentence_file,
             lax_segmentation,
             strict_pinyin,
+            keep_going,
+            fix,
+            format,
+            columns,
         } => {
-            let sentences = std::fs::read_to_string(sentence_file).unwrap();
-            let mut rest = sentences.as_str();
-            while !rest.trim().is_empty() {
-                if let Some((exercise, new_rest)) =
-                    Exercise::parse(rest, !lax_segmentation, !strict_pinyin)
-                {
-                    println!("{}", serde_yaml::to_string(&[exercise]).unwrap());
-                    rest = new_rest;
-                } else {
-                    panic!("Failed to parse at:\n{}", rest.trim());
+            let overrides = Overrides::load()?;
+            let format = format.unwrap_or_else(|| Format::guess(&sentence_file));
+            if fix && format != Format::Sentences {
+                return Err("--fix only repairs sentence files".into());
+            }
+            if fix {
+                fix::fix_sentence_file(
+                    &sentence_file,
+                    !lax_segmentation,
+                    !strict_pinyin,
+                    &overrides,
+                )?;
+            }
+            let sentences;
+            let mut sentence_file = if format == Format::Sentences {
+                sentences = std::fs::read_to_string(sentence_file)?;
+                SentenceFile::new(&sentences, !lax_segmentation, !strict_pinyin, &overrides)
+            } else {
+                let rows = import::read_rows(&sentence_file, format, &columns)?;
+                SentenceFile::from_rows(rows, !lax_segmentation, !strict_pinyin, &overrides)
+            };
+            let mut errors = vec![];
+            for result in sentence_file.by_ref() {
+                match result {
+                    Ok(exercise) => println!("{}", serde_yaml::to_string(&[exercise])?),
+                    Err(err) => {
+                        errors.push(err);
+                        if !keep_going {
+                            break;
+                        }
+                    }
                 }
             }
+            // Sentences without pinyin convert, but their guessed readings need checking.
+            for (line, ambiguity) in sentence_file.take_ambiguities() {
+                eprintln!("line {line}: {ambiguity}");
+            }
+            for err in &errors {
+                eprintln!("{err}\n");
+            }
+            if !errors.is_empty() {
+                eprintln!("{} sentences failed to convert.", errors.len());
+                std::process::exit(1);
+            }
         }
         Command::Sort { word_file } => {
             let dict = Dictionary::new();
@@ -118,6 +259,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
             word_file,
             exercise_file,
             frequency_sort,
+            scheduler,
+            mode,
+            blank_due,
         } => {
             // Chinese: 我是学生。
             // Pinyin:  wǒ shì xuéshēng.
@@ -136,7 +280,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
             let exercises: Vec<Exercise> = serde_yaml::from_str(&contents)?;
 
-            train(words, exercises)?;
+            train(words, exercises, scheduler, mode, blank_due, None)?;
         }
         Command::Audio { exercise_file } => {
             let mut file = File::open(exercise_file)?;
@@ -162,12 +306,109 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 validate_audio(&client, &sink, &exercise.english, None).await;
             }
         }
+        Command::Story {
+            story_file,
+            known_days,
+            restart,
+            audio,
+            scheduler,
+        } => {
+            let contents = std::fs::read_to_string(&story_file)?;
+            let exercises: Vec<Exercise> = serde_yaml::from_str(&contents)?;
+            if exercises.is_empty() {
+                return Err("The story has no sentences.".into());
+            }
+            if audio {
+                let missing = exercises
+                    .iter()
+                    .filter(|e| !audio_file_name(&e.chinese()).exists())
+                    .count();
+                if missing > 0 {
+                    return Err(format!(
+                        "{missing} sentences of the story have no audio. Run the audio command first."
+                    )
+                    .into());
+                }
+            }
+            let words = exercises
+                .iter()
+                .flat_map(|e| e.words())
+                .unique()
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let name = story_file.canonicalize()?.display().to_string();
+            let position = if restart {
+                0
+            } else {
+                let bookmarks = Bookmarks::load()?;
+                bookmarks.positions.get(&name).copied().unwrap_or(0)
+            };
+            let story = Story {
+                name,
+                position: position.min(exercises.len() - 1),
+                known_strength: chrono::Duration::seconds((known_days * 86400.0) as i64),
+            };
+            train(
+                words,
+                exercises,
+                scheduler,
+                if audio { Mode::Dictation } else { Mode::Pinyin },
+                false,
+                Some(story),
+            )?;
+        }
+        Command::Replay {
+            exercise_files,
+            scheduler,
+            output,
+        } => {
+            let mut exercises: Vec<Exercise> = vec![];
+            for exercise_file in exercise_files {
+                let contents = std::fs::read_to_string(exercise_file)?;
+                exercises.extend(serde_yaml::from_str::<Vec<Exercise>>(&contents)?);
+            }
+
+            let reviews = ReviewLog::open_default()?.load()?;
+            let model = model::UserModel::new()
+                .with_scheduler(scheduler)
+                .with_profile(&Profile::load()?)
+                .replay(&reviews, &exercises);
+            if let Some(output) = output {
+                model.save_to_file(output)?;
+            } else {
+                model.store()?;
+            }
+
+            let words = reviews.iter().map(|r| &r.word).unique().count();
+            println!("Replayed {} reviews of {} words.", reviews.len(), words);
+        }
+        Command::Optimize => {
+            let reviews = ReviewLog::open_default()?.load()?;
+            let mut profile = Profile::load()?;
+            let Some(before) = optimize::log_loss(&profile.classic, &reviews) else {
+                println!("Not enough reviews to fit scheduler parameters.");
+                return Ok(());
+            };
+            let params = optimize::fit(&profile.classic, &reviews);
+            let after = optimize::log_loss(&params, &reviews).unwrap_or(before);
+            println!("Log-loss before: {:.4}", before);
+            println!("Log-loss after:  {:.4}", after);
+            println!("{}", serde_yaml::to_string(&params)?);
+            profile.classic = params;
+            profile.store()?;
+        }
         Command::Tile {
             word_file,
             exercise_files,
             assumed_file,
             output_format,
             frequency_sort,
+            report,
+            optimize,
+            time_budget,
+            min_exposures,
+            spacing,
         } => {
             let dict = Dictionary::new();
 
@@ -186,82 +427,120 @@ async fn main() -> Result<(), Box<dyn Error>> {
             } else {
                 vec![]
             };
+            let index = ExerciseIndex::new(exercises, words);
+            let picks = if optimize {
+                let optimized = tile::optimize(&index, &assumed_words, time_budget);
+                eprintln!("Greedy course: {}.", optimized.greedy_cost);
+                eprintln!(
+                    "Optimized course (beam width {}): {}.",
+                    optimized.beam_width, optimized.cost
+                );
+                optimized.picks
+            } else {
+                tile::greedy(&index, &assumed_words)
+            };
+            let picks = if min_exposures > 1 {
+                tile::spread_exposures(&index, &assumed_words, picks, min_exposures, spacing)
+            } else {
+                picks
+            };
+            if report {
+                let report = tile::report(&index, &assumed_words, &picks, min_exposures);
+                match output_format {
+                    OutputFormat::Human => println!("{report}"),
+                    OutputFormat::Csv => {
+                        let mut writer = csv::Writer::from_writer(std::io::stdout());
+                        for row in report.rows() {
+                            writer.serialize(row)?;
+                        }
+                        writer.flush()?;
+                    }
+                    OutputFormat::Yaml => print!("{}", serde_yaml::to_string(&report)?),
+                }
+                return Ok(());
+            }
+
+            // Exercises are scored the way the trainer scores them, as if every word met so far
+            // had been learned.
             let mut model = model::UserModel::new();
             let now = Utc::now();
-            for word in assumed_words {
-                let prof = model.with_proficiency(&word, now);
-                prof.success(now);
-            }
-            loop {
-                let word = model.next_word(now, &words);
-                if model.seen(&word) {
-                    break;
+            for word in &assumed_words {
+                for skill in Skill::PINYIN {
+                    model.review(word, skill, Grade::Perfect, now);
                 }
-                model.with_proficiency(&word, now).success(now);
-
-                let mut alt_model = model.clone();
-                for _ in 0..0 {
-                    let word = alt_model.next_word(now, &words);
-                    println!("{}", word);
-                    alt_model.with_proficiency(&word, now).success(now);
+            }
+            for pick in picks {
+                let word = &pick.word;
+                for skill in Skill::PINYIN {
+                    model.review(word, skill, Grade::Perfect, now);
                 }
-                let exercise = alt_model
-                    .next_exercise(now, &exercises, &words, &word)
-                    .unwrap();
-                let score = alt_model.score_exercise(now, &exercise, &words);
-                model.mark_seen(&exercise, now);
+                let Some(exercise) = pick.exercise else {
+                    if let OutputFormat::Human = output_format {
+                        println!("{}", word);
+                        println!("  No exercises.");
+                    }
+                    continue;
+                };
+                let score = model.score_exercise(now, &index, exercise);
+                let exercise = &index.exercises()[exercise];
+                model.mark_seen(exercise, now);
                 for word in exercise.words() {
-                    model.with_proficiency(&word, now).success(now);
+                    for skill in Skill::PINYIN {
+                        model.review(word, skill, Grade::Perfect, now);
+                    }
                 }
                 match output_format {
+                    OutputFormat::Human if pick.review => println!("{} (again)", word),
                     OutputFormat::Human => {
                         println!("{}", word);
-                        // if costs.is_empty() {
-                        //     anes::execute!(
-                        //         std::io::stdout(),
-                        //         SetForegroundColor(Color::Red),
-                        //         "  No exercises.\n",
-                        //         ResetAttributes,
-                        //     )?;
-                        // } else if costs[0].1.n_novel_words == 0 {
-                        //     let e = costs[0].0;
-                        //     execute!(
-                        //         std::io::stdout(),
-                        //         SetForegroundColor(Color::Green),
-                        //         "  Free: ",
-                        //         ResetAttributes,
-                        //     )?;
-                        //     println!("{}", e.english);
-                        // } else {
-                        //     execute!(
-                        //         std::io::stdout(),
-                        //         SetForegroundColor(Color::Yellow),
-                        //         "  Costly\n",
-                        //         ResetAttributes,
-                        //     )?;
-                        //     for cost in costs.iter().take(5) {
-                        //         let e = cost.0;
-                        //         println!("  {} {:?}", e.english, cost.1);
-                        //     }
-                        // }
                     }
-                    OutputFormat::CSV => {
+                    OutputFormat::Csv => {
                         print!(
                             "{}/{}/{}\t",
                             score.words_not_in_list, score.words_in_list, score.words_not_seen
                         );
                         println!("{}\t{}\t{}", word, exercise.english, exercise.chinese());
-                        // course.push_exercise(exercise.clone());
                     }
-                    OutputFormat::YAML => {
+                    OutputFormat::Yaml => {
                         println!("{}", serde_yaml::to_string(&[exercise]).unwrap());
-                        // course.push_exercise(exercise);
                     }
                 }
             }
-
-            // let contents = std::fs::read_to_string(exercise_file)?;
-            // let exercises: Vec<Exercise> = serde_yaml::from_str(&contents)?;
+        }
+        Command::Lint {
+            exercise_files,
+            output_format,
+        } => {
+            let mut files = vec![];
+            for exercise_file in exercise_files {
+                let contents = std::fs::read_to_string(&exercise_file)?;
+                let exercises = serde_yaml::from_str::<Vec<Exercise>>(&contents)?;
+                files.push((exercise_file.display().to_string(), exercises));
+            }
+            let problems = lint::lint(&files, &Overrides::load()?);
+            let errors = problems
+                .iter()
+                .filter(|problem| problem.severity == Severity::Error)
+                .count();
+            match output_format {
+                OutputFormat::Human => {
+                    for problem in &problems {
+                        println!("{problem}");
+                    }
+                    println!("{} errors, {} warnings.", errors, problems.len() - errors);
+                }
+                OutputFormat::Csv => {
+                    let mut writer = csv::Writer::from_writer(std::io::stdout());
+                    for problem in &problems {
+                        writer.serialize(problem)?;
+                    }
+                    writer.flush()?;
+                }
+                OutputFormat::Yaml => print!("{}", serde_yaml::to_string(&problems)?),
+            }
+            if errors > 0 {
+                std::process::exit(1);
+            }
         }
     }
     Ok(())
@@ -309,10 +588,15 @@ fn audio_file_name(text: &str) -> PathBuf {
             .filter(|c| c.is_alphanumeric() || c.is_whitespace())
             .collect::<String>()
             .to_lowercase()
-            .replace(" ", "_")
+            .replace(' ', "_")
     ))
 }
 
+fn parse_seconds(arg: &str) -> Result<std::time::Duration, String> {
+    let seconds: f64 = arg.parse().map_err(|err| format!("{err}"))?;
+    std::time::Duration::try_from_secs_f64(seconds).map_err(|err| format!("{err}"))
+}
+
 fn load_words(dict: &Dictionary, file: PathBuf) -> anyhow::Result<Vec<String>> {
     let contents = std::fs::read_to_string(file)?;
     let entries = dict.segment(&contents);
@@ -323,71 +607,3 @@ fn load_words(dict: &Dictionary, file: PathBuf) -> anyhow::Result<Vec<String>> {
         .map(|e| e.simplified().to_string())
         .collect::<Vec<_>>())
 }
-
-#[derive(Debug, Ord, PartialOrd, PartialEq, Eq)]
-struct ExerciseCost {
-    // Freq cost of the least used new word.
-    word_freq_cost: OrderedFloat<f64>,
-    // Number of new words _not_ in course and _not_ in seen exercises.
-    n_novel_words: usize,
-    // Number of new words _not_ in seen exercises but _in_ course.
-    n_future_words: usize,
-    // Number of words _not_ in course but _in_ seen exercises.
-    n_extraneous_words: usize,
-    // Number of words in the exercise.
-    n_total_words: usize,
-}
-
-// Each exercise has a set of words. The "cost" of an exercise is the maximum cost of any word in
-// the exercise.
-struct Course {
-    course_exercises: Vec<Exercise>,
-    word_list: Vec<String>,
-}
-
-impl Course {
-    fn new(words: Vec<String>) -> Self {
-        Course {
-            course_exercises: vec![],
-            word_list: words,
-        }
-    }
-
-    fn push_exercise(&mut self, exercise: Exercise) {
-        self.course_exercises.push(exercise);
-    }
-
-    fn exercise_cost(&self, target_word: &str, exercise: &Exercise) -> ExerciseCost {
-        let mut seen_words = self
-            .course_exercises
-            .iter()
-            .flat_map(|e| e.words())
-            .collect::<Vec<_>>();
-        let target_word = target_word.to_string();
-        seen_words.push(&target_word);
-
-        let exercise_words = exercise.words();
-        let novel_words = exercise_words
-            .iter()
-            .filter(|w| !seen_words.contains(w))
-            .filter(|w| !self.word_list.contains(w))
-            .count();
-        let future_words = exercise_words
-            .iter()
-            .filter(|w| !seen_words.contains(w))
-            .filter(|w| self.word_list.contains(w))
-            .count();
-        let extraneous_words = exercise_words
-            .iter()
-            .filter(|w| seen_words.contains(w))
-            .filter(|w| !self.word_list.contains(w))
-            .count();
-        ExerciseCost {
-            word_freq_cost: OrderedFloat(0_f64),
-            n_novel_words: novel_words,
-            n_future_words: future_words,
-            n_extraneous_words: extraneous_words,
-            n_total_words: exercise.chinese().chars().count(),
-        }
-    }
-}
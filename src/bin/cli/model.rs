@@ -1,33 +1,77 @@
 use chrono::{DateTime, Duration, Utc};
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
-use serde_yaml;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::{Read, Write};
 use std::path::Path;
 
 use crate::convert::Exercise;
+use crate::index::ExerciseIndex;
+use crate::profile::Profile;
+use crate::review_log::Review;
+use crate::scheduler::{Classic, FsrsState, SchedulerKind, Schedulers, Sm2State};
+
+/// How well a word was recalled in a single answer. Grades are ordered from best to worst so the
+/// worst mistake made while answering can be kept with `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Grade {
+    // Correct on the first try.
+    Perfect,
+    // Correct after fixing a typo.
+    Typo,
+    // Correct, but took a long time to answer.
+    Slow,
+    // Syllables were right but at least one tone was wrong.
+    WrongTone,
+    // Correct only after the answer was revealed.
+    Hinted,
+    // Not recalled at all.
+    Wrong,
+}
+
+/// What is being recalled about a word. Each skill has its own proficiency, so a wrong tone is not
+/// confused with not recognising the characters at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Skill {
+    // Pinyin syllables of the word, ignoring tones.
+    Syllables,
+    // Tones of the word.
+    Tones,
+    // Writing the characters of the word.
+    Hanzi,
+    // Knowing what the word means.
+    Meaning,
+}
+
+impl Skill {
+    pub const ALL: [Skill; 4] = [Skill::Syllables, Skill::Tones, Skill::Hanzi, Skill::Meaning];
+    // Skills trained by typing the pinyin of a word.
+    pub const PINYIN: [Skill; 2] = [Skill::Syllables, Skill::Tones];
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Proficiency {
-    target_date: DateTime<Utc>,
-    memory_strength: Duration,
+    // State of the classic scheduler. Words are due at `target_date`.
+    pub target_date: DateTime<Utc>,
+    pub memory_strength: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sm2: Option<Sm2State>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fsrs: Option<FsrsState>,
 }
 
 impl Proficiency {
-    pub fn fail(&mut self, at: DateTime<Utc>) {
-        self.memory_strength = Duration::seconds(5);
-        self.target_date = at + self.memory_strength;
-    }
-
-    pub fn success(&mut self, at: DateTime<Utc>) {
-        if self.target_date > at {
-            self.memory_strength += self.memory_strength / 50;
-        } else {
-            self.memory_strength += self.memory_strength * 4;
+    /// A word seen for the first time at `at`.
+    pub fn new(at: DateTime<Utc>, memory_strength: Duration) -> Self {
+        Proficiency {
+            target_date: at,
+            memory_strength,
+            sm2: None,
+            fsrs: None,
         }
-        self.target_date = at + self.memory_strength;
     }
 }
 
@@ -45,6 +89,7 @@ pub struct ExerciseScore {
     pub future_words_count: usize,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WordListStatus {
     // Number of unique words in the word list
     pub total_words: usize,
@@ -52,16 +97,74 @@ pub struct WordListStatus {
     pub known_words: usize,
     // Number of words with a repetition scheduled in the past
     pub words_to_review: usize,
+    // Number of words with a repetition scheduled within the next hour
+    pub words_due_soon: usize,
     // Number of unique exercises that contain at least one word from the word list _and_ has been seen by the user.
     pub seen_sentences: usize,
     // Number of unique exercises that contain at least one word from the word list _and_ contains no unseen words.
     pub unlocked_sentences: usize,
+    // Number of unlocked exercises whose last unseen word was first seen today, in local time.
+    pub unlocked_today: usize,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+// Models saved before skills were tracked separately have a single proficiency per word. It was
+// trained by typing pinyin, so it is used for both pinyin skills.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSkills {
+    Skills(BTreeMap<Skill, Proficiency>),
+    Legacy(Proficiency),
+}
+
+fn deserialize_seen_words<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, BTreeMap<Skill, Proficiency>>, D::Error> {
+    let stored = HashMap::<String, StoredSkills>::deserialize(deserializer)?;
+    Ok(stored
+        .into_iter()
+        .map(|(word, skills)| {
+            let skills = match skills {
+                StoredSkills::Skills(skills) => skills,
+                StoredSkills::Legacy(prof) => Skill::PINYIN
+                    .iter()
+                    .map(|&skill| (skill, prof.clone()))
+                    .collect(),
+            };
+            (word, skills)
+        })
+        .collect())
+}
+
+// Models saved before exercises had ids keep the whole exercise as the key. Those exercises have
+// no id of their own, so they are identified by their Chinese text.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredExercises {
+    Ids(HashMap<String, DateTime<Utc>>),
+    Legacy(HashMap<Exercise, DateTime<Utc>>),
+}
+
+fn deserialize_seen_exercises<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, DateTime<Utc>>, D::Error> {
+    Ok(match StoredExercises::deserialize(deserializer)? {
+        StoredExercises::Ids(seen) => seen,
+        StoredExercises::Legacy(seen) => seen
+            .into_iter()
+            .map(|(exercise, at)| (exercise.id(), at))
+            .collect(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct UserModel {
-    seen_words: HashMap<String, Proficiency>,
-    seen_exercises: HashMap<Exercise, DateTime<Utc>>,
+    #[serde(deserialize_with = "deserialize_seen_words")]
+    seen_words: HashMap<String, BTreeMap<Skill, Proficiency>>,
+    // Exercise id -> when the exercise was last seen.
+    #[serde(deserialize_with = "deserialize_seen_exercises")]
+    seen_exercises: HashMap<String, DateTime<Utc>>,
+    #[serde(skip)]
+    schedulers: Schedulers,
 }
 
 impl UserModel {
@@ -69,20 +172,60 @@ impl UserModel {
         Self {
             seen_words: HashMap::new(),
             seen_exercises: HashMap::new(),
+            schedulers: Schedulers::default(),
         }
     }
 
-    pub fn with_proficiency<'a>(
-        &'a mut self,
-        word: &str,
-        now: DateTime<Utc>,
-    ) -> &'a mut Proficiency {
-        self.seen_words.entry(word.to_string()).or_insert({
-            Proficiency {
-                target_date: now,
-                memory_strength: Duration::seconds(5),
-            }
-        })
+    /// Use the given scheduler to decide when words are due.
+    pub fn with_scheduler(mut self, kind: SchedulerKind) -> Self {
+        self.schedulers = Schedulers::new(kind);
+        self
+    }
+
+    /// Use the scheduler parameters from the user's profile.
+    pub fn with_profile(mut self, profile: &Profile) -> Self {
+        self.schedulers.classic = Classic::new(profile.classic.clone());
+        self
+    }
+
+    /// When the word is next due for any of the given skills. None if the word has not been seen
+    /// for any of them.
+    pub fn due_date(&self, word: &str, skills: &[Skill]) -> Option<DateTime<Utc>> {
+        let proficiencies = self.seen_words.get(word)?;
+        skills
+            .iter()
+            .filter_map(|skill| proficiencies.get(skill))
+            .map(|prof| self.schedulers.active().due_date(prof))
+            .min()
+    }
+
+    /// Record an answer for one skill of a word. Every scheduler updates its own state.
+    pub fn review(&mut self, word: &str, skill: Skill, grade: Grade, now: DateTime<Utc>) {
+        let prof = self
+            .seen_words
+            .entry(word.to_string())
+            .or_default()
+            .entry(skill)
+            .or_insert_with(|| Proficiency::new(now, self.schedulers.classic.initial_strength()));
+        for scheduler in self.schedulers.all() {
+            scheduler.review(prof, grade, now);
+        }
+    }
+
+    // Whether the word has been seen for any of the given skills and one of them is due.
+    pub fn is_due(&self, word: &str, skills: &[Skill], at: DateTime<Utc>) -> bool {
+        self.due_date(word, skills).map_or(false, |due| due <= at)
+    }
+
+    // How long the word is remembered for the weakest of the given skills, as estimated by the
+    // classic scheduler. None if the word has not been seen for all of them.
+    pub fn memory_strength(&self, word: &str, skills: &[Skill]) -> Option<Duration> {
+        let proficiencies = self.seen_words.get(word)?;
+        skills
+            .iter()
+            .map(|skill| proficiencies.get(skill).map(|prof| prof.memory_strength))
+            .min()
+            .flatten()
     }
 
     pub fn seen(&self, word: &str) -> bool {
@@ -117,18 +260,18 @@ impl UserModel {
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
         let data_dir = Self::get_data_dir()?;
         let file_path = data_dir.join("user_model.yaml");
-        Self::load_from_file(&file_path)
+        Self::load_from_file(file_path)
     }
 
     /// Save UserModel to the default application data directory
     pub fn store(&self) -> Result<(), Box<dyn std::error::Error>> {
         let data_dir = Self::get_data_dir()?;
         let file_path = data_dir.join("user_model.yaml");
-        self.save_to_file(&file_path)
+        self.save_to_file(file_path)
     }
 
     /// Get the application data directory, creating it if it doesn't exist
-    fn get_data_dir() -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    pub fn get_data_dir() -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
         let project_dirs = ProjectDirs::from("com", "erudify", "erudify")
             .ok_or("Failed to get project directories")?;
 
@@ -140,39 +283,80 @@ impl UserModel {
         Ok(data_dir.to_path_buf())
     }
 
+    #[cfg(test)]
+    /// Progress through the word list, counting proficiency in the given skills only. The reviews
+    /// tell when words were first seen. Every word and exercise is looked at, so the trainer keeps
+    /// a StatusTracker instead, which must agree with this.
     pub fn status(
         &self,
-        exercises: &[Exercise],
-        word_list: &[String],
+        index: &ExerciseIndex,
+        reviews: &[Review],
         at: DateTime<Utc>,
+        skills: &[Skill],
     ) -> WordListStatus {
-        let total_words = word_list.len();
+        let total_words = index.word_list().len();
         let known_words = self
             .seen_words
-            .iter()
-            .filter(|(word, prof)| word_list.contains(word) && prof.target_date > at)
+            .keys()
+            .filter(|word| index.in_list(word))
+            .filter(|word| self.due_date(word, skills).map_or(false, |due| due > at))
             .count();
         let words_to_review = self
             .seen_words
-            .iter()
-            .filter(|(word, prof)| word_list.contains(word) && prof.target_date <= at)
+            .keys()
+            .filter(|word| index.in_list(word))
+            .filter(|word| self.is_due(word, skills, at))
             .count();
+        let words_due_soon = self
+            .seen_words
+            .keys()
+            .filter(|word| index.in_list(word))
+            .filter(|word| {
+                self.due_date(word, skills)
+                    .map_or(false, |due| due > at && due <= at + Duration::hours(1))
+            })
+            .count();
+
+        let mut first_seen: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        for review in reviews {
+            let reviewed_skills = match &review.skill {
+                Some(skill) => std::slice::from_ref(skill),
+                None => &Skill::PINYIN[..],
+            };
+            if reviewed_skills.iter().any(|skill| skills.contains(skill)) {
+                let first = first_seen.entry(&review.word).or_insert(review.timestamp);
+                *first = (*first).min(review.timestamp);
+            }
+        }
+        let today = at.with_timezone(&chrono::Local).date_naive();
 
         let mut seen_sentences_set = HashSet::new();
         let mut unlocked_sentences_set = HashSet::new();
+        let mut unlocked_today_set = HashSet::new();
 
-        for exercise in exercises {
-            let exercise_words = exercise.words();
-            if exercise_words.iter().any(|word| word_list.contains(word)) {
-                if exercise_words
+        for (n, exercise) in index.exercises().iter().enumerate() {
+            let exercise_words = index.words(n);
+            if exercise_words.iter().any(|word| index.in_list(word))
+                && exercise_words
                     .iter()
-                    .all(|word| self.seen_words.contains_key(word.as_str()))
-                {
-                    if self.seen_exercises.contains_key(exercise) {
-                        seen_sentences_set.insert(exercise.clone());
-                    }
-                    unlocked_sentences_set.insert(exercise.clone());
+                    .all(|word| self.due_date(word, skills).is_some())
+            {
+                if self.seen_exercises.contains_key(&exercise.id()) {
+                    seen_sentences_set.insert(exercise.id());
+                }
+                // The exercise was unlocked when the last of its words was first seen.
+                let unlocked = exercise_words
+                    .iter()
+                    .map(|word| {
+                        let seen_at = first_seen.get(word.as_str()).copied();
+                        seen_at.unwrap_or_default()
+                    })
+                    .max()
+                    .unwrap_or_default();
+                if unlocked.with_timezone(&chrono::Local).date_naive() == today {
+                    unlocked_today_set.insert(exercise.id());
                 }
+                unlocked_sentences_set.insert(exercise.id());
             }
         }
 
@@ -180,56 +364,98 @@ impl UserModel {
             total_words,
             known_words,
             words_to_review,
+            words_due_soon,
             seen_sentences: seen_sentences_set.len(),
             unlocked_sentences: unlocked_sentences_set.len(),
+            unlocked_today: unlocked_today_set.len(),
         }
     }
 
     pub fn mark_seen(&mut self, exercise: &Exercise, at: DateTime<Utc>) {
-        self.seen_exercises.insert(exercise.clone(), at);
+        self.seen_exercises.insert(exercise.id(), at);
+    }
+
+    pub fn seen_exercise(&self, exercise: &Exercise) -> bool {
+        self.seen_exercises.contains_key(&exercise.id())
+    }
+
+    /// Apply logged reviews to the model, e.g. to rebuild it from an empty model. Exercises are
+    /// marked as seen at the time of their last review; reviews of exercises that are not in
+    /// `exercises` only update word proficiencies.
+    pub fn replay(mut self, reviews: &[Review], exercises: &[Exercise]) -> Self {
+        // Reviews logged before exercises had ids name the exercise by its Chinese text.
+        let exercises = exercises
+            .iter()
+            .map(|exercise| (exercise.chinese(), exercise))
+            .chain(exercises.iter().map(|exercise| (exercise.id(), exercise)))
+            .collect::<HashMap<_, _>>();
+        for review in reviews {
+            match review.skill {
+                Some(skill) => self.review(&review.word, skill, review.grade, review.timestamp),
+                None => {
+                    for skill in Skill::PINYIN {
+                        self.review(&review.word, skill, review.grade, review.timestamp);
+                    }
+                }
+            }
+            if let Some(exercise) = exercises.get(&review.exercise) {
+                self.mark_seen(exercise, review.timestamp);
+            }
+        }
+        self
     }
 
-    /// Calculate the score for an exercise based on the prioritization criteria.
+    /// Calculate the score for an exercise of the index based on the prioritization criteria.
     /// Lower scores are better (we want to minimize each component in priority order).
     pub fn score_exercise(
         &self,
         now: DateTime<Utc>,
-        exercise: &Exercise,
-        word_list: &[String],
+        index: &ExerciseIndex,
+        exercise: usize,
+    ) -> ExerciseScore {
+        self.score_exercise_for(now, index, exercise, &Skill::ALL)
+    }
+
+    /// Like `score_exercise`, but only counting proficiency in the given skills.
+    pub fn score_exercise_for(
+        &self,
+        now: DateTime<Utc>,
+        index: &ExerciseIndex,
+        exercise: usize,
+        skills: &[Skill],
     ) -> ExerciseScore {
-        let exercise_words = exercise.words();
+        let exercise_words = index.words(exercise);
 
         // Count future words (lowest priority)
-        let future_words: HashSet<&&String> = exercise_words
+        let future_words: HashSet<&String> = exercise_words
             .iter()
-            .filter(|word| {
-                self.seen_words
-                    .get(**word)
-                    .map_or(false, |prof| prof.target_date > now)
-            })
+            .filter(|word| self.due_date(word, skills).map_or(false, |due| due > now))
             .collect();
 
         // Count unseen words
         let words_not_seen = exercise_words
             .iter()
-            .filter(|word| self.seen_words.get(**word).is_none())
+            .filter(|word| self.due_date(word, skills).is_none())
             .count();
 
         // Among unseen words, count those in word_list vs not in word_list
         let words_in_list = exercise_words
             .iter()
             .filter(|word| !future_words.contains(word))
-            .filter(|word| word_list.contains(**word))
+            .filter(|word| index.in_list(word))
             .count();
 
         let words_not_in_list = exercise_words
             .iter()
             .filter(|word| !future_words.contains(word))
-            .filter(|word| !word_list.contains(**word))
+            .filter(|word| !index.in_list(word))
             .count();
 
         // Get last seen date of the exercise
-        let last_seen_date = self.seen_exercises.get(exercise).copied();
+        let last_seen_date = self
+            .seen_exercises
+            .get(&index.exercises()[exercise].id())
+            .copied();
 
         ExerciseScore {
             words_not_in_list,
@@ -246,10 +472,10 @@ impl UserModel {
     pub fn set_target_date(&mut self, word: &str, target_date: DateTime<Utc>) {
         self.seen_words.insert(
             word.to_string(),
-            Proficiency {
-                target_date,
-                memory_strength: Duration::zero(),
-            },
+            Skill::ALL
+                .iter()
+                .map(|&skill| (skill, Proficiency::new(target_date, Duration::zero())))
+                .collect(),
         );
     }
 
@@ -258,6 +484,16 @@ impl UserModel {
     // If there's no such word, pick the next unseen word from the word_list.
     // If there are no unseen words, pick the seen word with the nearest 'target_date' in the future.
     pub fn next_word(&self, now: DateTime<Utc>, word_list: &[String]) -> String {
+        self.next_word_for(now, word_list, &Skill::ALL)
+    }
+
+    /// Like `next_word`, but a word counts as seen and due according to the given skills only.
+    pub fn next_word_for(
+        &self,
+        now: DateTime<Utc>,
+        word_list: &[String],
+        skills: &[Skill],
+    ) -> String {
         #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
         enum WordScore {
             // Seen, due for review (target_date <= now). Smaller diff is better (closer to now).
@@ -272,9 +508,8 @@ impl UserModel {
             .iter()
             .enumerate()
             .min_by_key(|(idx, word)| {
-                if let Some(prof) = self.seen_words.get(*word) {
-                    let target_date = prof.target_date;
-                    let diff = target_date - now;
+                if let Some(due_date) = self.due_date(word, skills) {
+                    let diff = due_date - now;
                     if diff <= chrono::Duration::zero() {
                         WordScore::SeenDue { diff: diff.abs() }
                     } else {
@@ -302,24 +537,37 @@ impl UserModel {
     //  - We minimize the number of words in the word_list (second priority).
     //  - We minimize the last_seen_date of the exercise (third priority)
     //  - We minimize the number of seen words with a target date in the future (fourth priority).
+    //
+    // Returns the position of the exercise in the index.
     pub fn next_exercise(
         &self,
         now: DateTime<Utc>,
-        exercises: &[Exercise],
-        word_list: &[String],
+        index: &ExerciseIndex,
+        target_word: &str,
+    ) -> Option<usize> {
+        self.next_exercise_for(now, index, target_word, &Skill::ALL)
+    }
+
+    /// Like `next_exercise`, but only counting proficiency in the given skills.
+    pub fn next_exercise_for(
+        &self,
+        now: DateTime<Utc>,
+        index: &ExerciseIndex,
         target_word: &str,
-    ) -> Option<Exercise> {
-        exercises
+        skills: &[Skill],
+    ) -> Option<usize> {
+        index
+            .with_word(target_word)
             .iter()
-            .filter(|exercise| exercise.words().contains(&&target_word.to_string()))
-            .min_by_key(|exercise| self.score_exercise(now, *exercise, word_list))
-            .cloned()
+            .copied()
+            .min_by_key(|&exercise| self.score_exercise_for(now, index, exercise, skills))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scheduler::Scheduler;
 
     // Helper functions for creating specific UserModel instances
 
@@ -437,7 +685,7 @@ mod tests {
         model.set_target_date("你好", target_date);
 
         // Verify the proficiency was inserted correctly
-        let proficiency = model.seen_words.get("你好").unwrap();
+        let proficiency = &model.seen_words["你好"][&Skill::Syllables];
         assert_eq!(proficiency.target_date, target_date);
 
         // Insert another word that should be due in 3 hours
@@ -445,7 +693,7 @@ mod tests {
         model.set_target_date("谢谢", future_target_date);
 
         // Verify the second proficiency was inserted correctly
-        let proficiency2 = model.seen_words.get("谢谢").unwrap();
+        let proficiency2 = &model.seen_words["谢谢"][&Skill::Syllables];
         assert_eq!(proficiency2.target_date, future_target_date);
 
         // Test that the word list prioritization works correctly
@@ -456,10 +704,47 @@ mod tests {
         assert_eq!(result, "你好");
     }
 
+    #[test]
+    fn test_is_due() {
+        let mut model = UserModel::new();
+        model.set_target_date("你好", now() - Duration::hours(2));
+        model.set_target_date("谢谢", now() + Duration::hours(3));
+
+        assert!(model.is_due("你好", &Skill::PINYIN, now()));
+        assert!(!model.is_due("谢谢", &Skill::PINYIN, now()));
+        assert!(!model.is_due("再见", &Skill::PINYIN, now()));
+
+        // Only the given skills count.
+        model.review(
+            "学习",
+            Skill::Hanzi,
+            Grade::Wrong,
+            now() - Duration::hours(1),
+        );
+        assert!(model.is_due("学习", &[Skill::Hanzi], now()));
+        assert!(!model.is_due("学习", &Skill::PINYIN, now()));
+    }
+
+    #[test]
+    fn test_memory_strength_of_weakest_skill() {
+        let mut model = UserModel::new();
+        assert_eq!(model.memory_strength("你好", &Skill::PINYIN), None);
+
+        model.review("你好", Skill::Syllables, Grade::Perfect, now());
+        assert_eq!(model.memory_strength("你好", &Skill::PINYIN), None);
+
+        model.review("你好", Skill::Tones, Grade::Wrong, now());
+        assert_eq!(
+            model.memory_strength("你好", &Skill::PINYIN),
+            Some(Duration::seconds(5))
+        );
+        assert!(model.memory_strength("你好", &[Skill::Syllables]).unwrap() > Duration::seconds(5));
+    }
+
     #[test]
     fn test_next_word_with_single_word_list() {
         assert_eq!(
-            UserModel::new().next_word(now(), &vec!["你好".to_string()]),
+            UserModel::new().next_word(now(), &["你好".to_string()]),
             "你好"
         );
     }
@@ -467,7 +752,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "word_list must not be empty")]
     fn test_next_word_empty_word_list_panics() {
-        UserModel::new().next_word(now(), &vec![]);
+        UserModel::new().next_word(now(), &[]);
     }
 
     fn wo_shi_xuesheng_exercise() -> Exercise {
@@ -504,13 +789,16 @@ mod tests {
         serde_yaml::from_str(yaml).expect("Failed to parse YAML into Exercise")
     }
 
+    fn index(exercises: &[Exercise], word_list: &[String]) -> ExerciseIndex {
+        ExerciseIndex::new(exercises.to_vec(), word_list.to_vec())
+    }
+
     #[test]
     fn test_next_exercise_empty_exercises_returns_none() {
         assert_eq!(
             UserModel::new().next_exercise(
                 now(),
-                &[],
-                &["你好".to_string(), "谢谢".to_string()],
+                &index(&[], &["你好".to_string(), "谢谢".to_string()]),
                 "你好"
             ),
             None
@@ -523,7 +811,7 @@ mod tests {
         let word_list = vec!["你好".to_string(), "谢谢".to_string()];
 
         assert_eq!(
-            UserModel::new().next_exercise(now(), &exercises, &word_list, "你好"),
+            UserModel::new().next_exercise(now(), &index(&exercises, &word_list), "你好"),
             None
         );
     }
@@ -537,16 +825,16 @@ mod tests {
         let word_list = vec!["我".to_string(), "喜欢".to_string(), "吃".to_string()];
 
         let result = UserModel::new()
-            .next_exercise(now(), &exercises, &word_list, "我")
+            .next_exercise(now(), &index(&exercises, &word_list), "我")
             .unwrap();
-        assert_eq!(result, exercises[0]);
+        assert_eq!(result, 0);
 
         // The order of the exercises should not matter.
         exercises.swap(0, 1);
         let result = UserModel::new()
-            .next_exercise(now(), &exercises, &word_list, "我")
+            .next_exercise(now(), &index(&exercises, &word_list), "我")
             .unwrap();
-        assert_eq!(result, exercises[1]);
+        assert_eq!(result, 1);
     }
 
     #[test]
@@ -559,9 +847,9 @@ mod tests {
         model.set_target_date("学生", now() + Duration::hours(2));
 
         let result = model
-            .next_exercise(now(), &exercises, &word_list, "我")
+            .next_exercise(now(), &index(&exercises, &word_list), "我")
             .unwrap();
-        assert_eq!(result, exercises[1]);
+        assert_eq!(result, 1);
     }
 
     #[test]
@@ -574,14 +862,15 @@ mod tests {
         model.set_target_date("学生", now() - Duration::hours(2));
 
         let result = model
-            .next_exercise(now(), &exercises, &word_list, "我")
+            .next_exercise(now(), &index(&exercises, &word_list), "我")
             .unwrap();
-        assert_eq!(result, exercises[0]);
+        assert_eq!(result, 0);
     }
 
     #[test]
     fn test_score_exercise_1() {
         let word_list = vec!["我".to_string(), "喜欢".to_string(), "吃".to_string()];
+        let index = index(&[wo_xihuan_chi_jiaozi_exercise()], &word_list);
 
         let mut model = UserModel::new();
         // Set "我" and "学生" as seen with past due dates
@@ -589,7 +878,7 @@ mod tests {
         model.set_target_date("喜欢", now() - Duration::hours(2));
 
         // Test the scoring method directly
-        let score = model.score_exercise(now(), &wo_xihuan_chi_jiaozi_exercise(), &word_list);
+        let score = model.score_exercise(now(), &index, 0);
 
         assert_eq!(score.future_words_count, 0);
         assert_eq!(score.words_in_list, 3); // "我", "喜欢", "吃"
@@ -600,13 +889,206 @@ mod tests {
         model.set_target_date("喜欢", now() + Duration::hours(2));
 
         // Test the scoring method directly
-        let score = model.score_exercise(now(), &wo_xihuan_chi_jiaozi_exercise(), &word_list);
+        let score = model.score_exercise(now(), &index, 0);
 
         assert_eq!(score.future_words_count, 2);
         assert_eq!(score.words_in_list, 1);
         assert_eq!(score.words_not_in_list, 1);
     }
 
+    #[test]
+    fn test_switching_scheduler_keeps_state() {
+        let word_list = vec!["你好".to_string()];
+        let mut model = UserModel::new();
+        model.review("你好", Skill::Syllables, Grade::Perfect, now());
+
+        // The classic scheduler shows a new word again after 25 seconds, SM-2 after a day.
+        let later = now() + Duration::hours(1);
+        assert_eq!(
+            model
+                .status(&index(&[], &word_list), &[], later, &Skill::ALL)
+                .known_words,
+            0
+        );
+        let yaml = serde_yaml::to_string(&model).expect("Failed to serialize");
+        let model = UserModel::load_from_reader(yaml.as_bytes())
+            .expect("Failed to deserialize")
+            .with_scheduler(SchedulerKind::Sm2);
+        assert_eq!(
+            model
+                .status(&index(&[], &word_list), &[], later, &Skill::ALL)
+                .known_words,
+            1
+        );
+    }
+
+    #[test]
+    fn test_replay_matches_live_reviews() {
+        let exercise = wo_shi_xuesheng_exercise();
+        let answers = [
+            ("我", Skill::Syllables, Grade::Perfect, now()),
+            (
+                "是",
+                Skill::Tones,
+                Grade::WrongTone,
+                now() + Duration::seconds(3),
+            ),
+            (
+                "学生",
+                Skill::Syllables,
+                Grade::Hinted,
+                now() + Duration::seconds(9),
+            ),
+            ("我", Skill::Hanzi, Grade::Slow, now() + Duration::days(2)),
+        ];
+
+        let mut live = UserModel::new();
+        let mut reviews = vec![];
+        for (word, skill, grade, at) in answers {
+            live.review(word, skill, grade, at);
+            live.mark_seen(&exercise, at);
+            reviews.push(Review {
+                timestamp: at,
+                word: word.to_string(),
+                skill: Some(skill),
+                exercise: exercise.chinese(),
+                input: String::new(),
+                grade,
+                latency_ms: 0,
+                hint_used: grade == Grade::Hinted,
+            });
+        }
+
+        let replayed = UserModel::new().replay(&reviews, &[exercise.clone()]);
+        assert_eq!(replayed, live);
+
+        // Without the exercise, only word proficiencies are restored.
+        let replayed = UserModel::new().replay(&reviews, &[]);
+        assert_eq!(replayed.seen_words, live.seen_words);
+        assert!(replayed.seen_exercises.is_empty());
+    }
+
+    #[test]
+    fn test_skills_are_scheduled_independently() {
+        let word_list = vec!["我".to_string(), "是".to_string()];
+        let mut model = UserModel::new();
+        model.review("我", Skill::Syllables, Grade::Perfect, now());
+        model.review("我", Skill::Tones, Grade::Wrong, now());
+
+        let later = now() + Duration::seconds(10);
+        let syllables = model.status(&index(&[], &word_list), &[], later, &[Skill::Syllables]);
+        assert_eq!((syllables.known_words, syllables.words_to_review), (1, 0));
+        let tones = model.status(&index(&[], &word_list), &[], later, &[Skill::Tones]);
+        assert_eq!((tones.known_words, tones.words_to_review), (0, 1));
+
+        // A word only counts as seen for the skills it was reviewed in.
+        assert_eq!(
+            model.next_word_for(later, &word_list, &[Skill::Hanzi]),
+            "我"
+        );
+        assert_eq!(
+            model.next_word_for(later, &word_list, &[Skill::Syllables]),
+            "是"
+        );
+        let score = model.score_exercise_for(
+            later,
+            &index(&[wo_shi_xuesheng_exercise()], &word_list),
+            0,
+            &[Skill::Syllables],
+        );
+        assert_eq!(score.words_not_seen, 2);
+        assert_eq!(score.future_words_count, 1);
+    }
+
+    #[test]
+    fn test_load_legacy_model() {
+        let yaml = r#"
+        seen_words:
+          你好:
+            target_date: 2024-01-15T12:00:00Z
+            memory_strength: [3600, 0]
+        seen_exercises: {}
+        "#;
+        let model = UserModel::load_from_reader(yaml.as_bytes()).expect("Failed to load");
+        let skills = &model.seen_words["你好"];
+        assert_eq!(skills.keys().copied().collect::<Vec<_>>(), Skill::PINYIN);
+        assert_eq!(skills[&Skill::Tones].target_date, now());
+        assert_eq!(skills[&Skill::Tones].memory_strength, Duration::hours(1));
+    }
+
+    #[test]
+    fn test_schedulers_seed_from_state_before_review() {
+        // A new word starts FSRS from scratch, not from the strength classic just gave it.
+        let mut model = UserModel::new();
+        model.review("你好", Skill::Tones, Grade::Perfect, now());
+        let mut fsrs_only = Proficiency::new(now(), model.schedulers.classic.initial_strength());
+        model
+            .schedulers
+            .fsrs
+            .review(&mut fsrs_only, Grade::Perfect, now());
+        assert!(fsrs_only.fsrs.is_some());
+        assert_eq!(model.seen_words["你好"][&Skill::Tones].fsrs, fsrs_only.fsrs);
+
+        // A legacy word is seeded from its state before the answer, so the answer counts once.
+        let yaml = r#"
+        seen_words:
+          你好:
+            target_date: 2024-01-15T12:00:00Z
+            memory_strength: [3600, 0]
+        seen_exercises: {}
+        "#;
+        let mut model = UserModel::load_from_reader(yaml.as_bytes()).expect("Failed to load");
+        let mut seeded = model.seen_words["你好"][&Skill::Tones].clone();
+        let later = now() + Duration::hours(2);
+        model.review("你好", Skill::Tones, Grade::Perfect, later);
+        model
+            .schedulers
+            .sm2
+            .review(&mut seeded, Grade::Perfect, later);
+        model
+            .schedulers
+            .fsrs
+            .review(&mut seeded, Grade::Perfect, later);
+        let prof = &model.seen_words["你好"][&Skill::Tones];
+        assert_eq!(prof.sm2, seeded.sm2);
+        assert_eq!(prof.fsrs, seeded.fsrs);
+    }
+
+    #[test]
+    fn test_load_model_keyed_by_exercise() {
+        let yaml = r#"
+        seen_words: {}
+        seen_exercises:
+          ? segments:
+              - chinese: 你好
+                pinyin: nǐ hǎo
+            english: Hello.
+          : 2024-01-15T12:00:00Z
+        "#;
+        let model = UserModel::load_from_reader(yaml.as_bytes()).expect("Failed to load");
+        assert_eq!(model.seen_exercises["你好"], now());
+
+        let yaml = serde_yaml::to_string(&model).expect("Failed to serialize");
+        assert_eq!(UserModel::load_from_reader(yaml.as_bytes()).unwrap(), model);
+    }
+
+    #[test]
+    fn test_edited_exercise_keeps_history() {
+        let mut model = UserModel::new();
+        let mut exercise = wo_shi_xuesheng_exercise();
+        model.mark_seen(&exercise, now());
+        exercise.english = "I'm a student.".to_string();
+        exercise.tags = vec!["hsk1".to_string()];
+        let score = model.score_exercise(now(), &index(&[exercise.clone()], &[]), 0);
+        assert_eq!(score.last_seen_date, Some(now()));
+
+        // An explicit id survives edits of the Chinese text too.
+        exercise.id = "student".to_string();
+        model.mark_seen(&exercise, now());
+        exercise.segments.pop();
+        assert!(model.seen_exercises.contains_key(&exercise.id()));
+    }
+
     #[test]
     fn test_serialization_roundtrip() {
         // Create a UserModel with some data
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use crate::model::{Grade, Proficiency, Skill};
+use crate::review_log::Review;
+use crate::scheduler::{Classic, ClassicParams, Scheduler};
+
+// Predictions are clamped to avoid infinite loss on a single surprising answer.
+const EPSILON: f64 = 1e-6;
+
+// Tone and typing mistakes still count as recalled. Only answers that needed the hint, or were not
+// given at all, count as forgotten.
+fn recalled(grade: Grade) -> bool {
+    !matches!(grade, Grade::Hinted | Grade::Wrong)
+}
+
+/// Mean log-loss of the classic scheduler's recall predictions over the review log. The first
+/// review of a word has nothing to predict from and is skipped. Returns None if no review could be
+/// predicted.
+pub fn log_loss(params: &ClassicParams, reviews: &[Review]) -> Option<f64> {
+    let classic = Classic::new(params.clone());
+    let mut words: HashMap<(&str, Option<Skill>), Proficiency> = HashMap::new();
+    let mut total = 0.0;
+    let mut count = 0;
+    for review in reviews {
+        let key = (review.word.as_str(), review.skill);
+        let Some(prof) = words.get_mut(&key) else {
+            let mut prof = Proficiency::new(review.timestamp, classic.initial_strength());
+            classic.review(&mut prof, review.grade, review.timestamp);
+            words.insert(key, prof);
+            continue;
+        };
+        let p = classic
+            .recall_probability(prof, review.timestamp)
+            .clamp(EPSILON, 1.0 - EPSILON);
+        total -= if recalled(review.grade) {
+            p.ln()
+        } else {
+            (1.0 - p).ln()
+        };
+        count += 1;
+        classic.review(prof, review.grade, review.timestamp);
+    }
+    (count > 0).then(|| total / count as f64)
+}
+
+// The fitted parameters with the range they are searched in.
+fn param_mut(params: &mut ClassicParams, index: usize) -> (&mut f64, f64, f64) {
+    match index {
+        0 => (&mut params.initial_strength_secs, 1.0, 86400.0),
+        1 => (&mut params.early_growth, 0.001, 1.0),
+        _ => (&mut params.late_growth, 0.1, 50.0),
+    }
+}
+
+/// Fit the classic scheduler parameters to the review log by minimizing the log-loss of its recall
+/// predictions. Starts from `initial` and does a coordinate search in log space, halving the step
+/// size (in log space) whenever no parameter can be improved.
+pub fn fit(initial: &ClassicParams, reviews: &[Review]) -> ClassicParams {
+    let mut best = initial.clone();
+    let Some(mut best_loss) = log_loss(&best, reviews) else {
+        return best;
+    };
+    let mut step = 2.0_f64;
+    while step > 1.01 {
+        let mut improved = false;
+        for index in 0..3 {
+            for factor in [step, 1.0 / step] {
+                let mut candidate = best.clone();
+                let (value, min, max) = param_mut(&mut candidate, index);
+                *value = (*value * factor).clamp(min, max);
+                match log_loss(&candidate, reviews) {
+                    Some(loss) if loss < best_loss => {
+                        best = candidate;
+                        best_loss = loss;
+                        improved = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if !improved {
+            step = step.sqrt();
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn review(word: &str, grade: Grade, at: DateTime<Utc>) -> Review {
+        Review {
+            timestamp: at,
+            word: word.to_string(),
+            skill: Some(Skill::Syllables),
+            exercise: String::new(),
+            input: String::new(),
+            grade,
+            latency_ms: 0,
+            hint_used: grade == Grade::Hinted,
+        }
+    }
+
+    // A learner who still remembers every word long after it was due.
+    fn strong_memory_reviews() -> Vec<Review> {
+        let mut reviews = vec![];
+        for (n, word) in ["我", "是", "学生", "喜欢"].into_iter().enumerate() {
+            let mut at = now() + Duration::minutes(n as i64);
+            let mut gap = Duration::minutes(1);
+            for _ in 0..6 {
+                reviews.push(review(word, Grade::Perfect, at));
+                at += gap;
+                gap = gap * 20;
+            }
+        }
+        reviews.sort_by_key(|r| r.timestamp);
+        reviews
+    }
+
+    #[test]
+    fn test_log_loss_needs_repeated_reviews() {
+        let reviews = vec![
+            review("我", Grade::Perfect, now()),
+            review("是", Grade::Wrong, now()),
+        ];
+        assert_eq!(log_loss(&ClassicParams::default(), &reviews), None);
+        assert_eq!(log_loss(&ClassicParams::default(), &[]), None);
+    }
+
+    #[test]
+    fn test_log_loss_penalizes_surprises() {
+        let forgotten = vec![
+            review("我", Grade::Perfect, now()),
+            review("我", Grade::Wrong, now() + Duration::seconds(10)),
+        ];
+        let remembered = vec![
+            review("我", Grade::Perfect, now()),
+            review("我", Grade::Perfect, now() + Duration::seconds(10)),
+        ];
+        let params = ClassicParams::default();
+        assert!(log_loss(&params, &forgotten).unwrap() > log_loss(&params, &remembered).unwrap());
+    }
+
+    #[test]
+    fn test_fit_improves_log_loss() {
+        let reviews = strong_memory_reviews();
+        let before = ClassicParams::default();
+        let after = fit(&before, &reviews);
+        assert!(log_loss(&after, &reviews).unwrap() < log_loss(&before, &reviews).unwrap());
+        // Memories last longer than the default parameters assume.
+        assert!(after.late_growth > before.late_growth);
+    }
+
+    #[test]
+    fn test_fit_without_predictions_keeps_parameters() {
+        let params = ClassicParams::default();
+        assert_eq!(fit(&params, &[review("我", Grade::Perfect, now())]), params);
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::model::UserModel;
+use crate::scheduler::ClassicParams;
+
+/// Per-user settings that are not part of the learner state, such as fitted scheduler parameters.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub classic: ClassicParams,
+}
+
+impl Profile {
+    /// Load Profile from a YAML file
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = fs::File::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// Save Profile to a YAML file
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::File::create(path)?;
+        serde_yaml::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Load Profile from the default application data directory. A missing file gives the
+    /// default settings.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = UserModel::get_data_dir()?.join("profile.yaml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load_from_file(path)
+    }
+
+    /// Save Profile to the default application data directory
+    pub fn store(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to_file(UserModel::get_data_dir()?.join("profile.yaml"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_save_file() {
+        let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+        let profile = Profile {
+            classic: ClassicParams {
+                initial_strength_secs: 12.5,
+                early_growth: 0.05,
+                late_growth: 2.5,
+            },
+        };
+        profile
+            .save_to_file(temp_file.path())
+            .expect("Failed to save");
+        assert_eq!(Profile::load_from_file(temp_file.path()).unwrap(), profile);
+    }
+
+    #[test]
+    fn test_missing_parameters_use_defaults() {
+        let profile: Profile = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(profile, Profile::default());
+    }
+}
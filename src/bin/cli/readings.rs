@@ -0,0 +1,208 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::convert::strip_tone;
+use crate::model::UserModel;
+
+// Built-in overrides, in the same format as the user's readings.yaml.
+const BUILT_IN: &str = include_str!("readings.yaml");
+
+/// Readings the user accepts for words in addition to the dictionary's. Corrects dictionary
+/// readings that sentence sources disagree with, like 因素: yīn shù.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Overrides {
+    // Word -> reading, syllables separated by spaces.
+    #[serde(default)]
+    pub readings: BTreeMap<String, String>,
+    // Words whose last syllable is usually written with the neutral tone, although the dictionary
+    // gives it a full tone. Other words must be written as one of their readings, so that 中国
+    // can't be answered as zhōngguo.
+    #[serde(default)]
+    pub neutral_tone: BTreeSet<String>,
+}
+
+impl Default for Overrides {
+    /// The built-in overrides
+    fn default() -> Self {
+        serde_yaml::from_str(BUILT_IN).expect("Built-in readings.yaml is invalid")
+    }
+}
+
+impl Overrides {
+    /// Load Overrides from a YAML file, in addition to the built-in ones
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = fs::File::open(path)?;
+        let user: Self = serde_yaml::from_reader(file)?;
+        let mut overrides = Self::default();
+        overrides.readings.extend(user.readings);
+        overrides.neutral_tone.extend(user.neutral_tone);
+        Ok(overrides)
+    }
+
+    /// Load Overrides from the default application data directory. A missing file leaves the
+    /// built-in ones.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = UserModel::get_data_dir()?.join("readings.yaml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load_from_file(path)
+    }
+
+    /// Override readings of the words `text` starts with.
+    pub fn prefixes<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.readings
+            .iter()
+            .filter(move |(word, _)| text.starts_with(word.as_str()))
+            .map(|(word, reading)| (word.as_str(), reading.as_str()))
+    }
+
+    /// Every way of writing the pinyin of a word that is accepted as correct: the reading itself,
+    /// its tone sandhi and neutral tone variants, and the user's override for the word.
+    pub fn accepted(&self, word: &str, reading: &str) -> Vec<String> {
+        let mut accepted = self.variants(word, reading);
+        if let Some(reading) = self.readings.get(word) {
+            accepted.extend(self.variants(word, reading));
+        }
+        accepted.into_iter().unique().collect()
+    }
+
+    // The citation reading followed by the other accepted ways of writing it:
+    //  - 一 and 不 change tone before other syllables of the word. On their own, the next word
+    //    decides the tone, so any of their sandhi tones is accepted.
+    //  - The last syllable of the neutral tone words may be written with the neutral tone.
+    //
+    // variants("一定", "yī dìng") -> ["yī dìng", "yí dìng"]
+    // variants("不断", "bù duàn") -> ["bù duàn", "bú duàn"]
+    // variants("个", "gè") -> ["gè", "ge"]
+    pub fn variants(&self, word: &str, reading: &str) -> Vec<String> {
+        let chars = word.chars().collect::<Vec<_>>();
+        let syllables = reading.split(' ').collect::<Vec<_>>();
+
+        let mut sandhi: Vec<Vec<String>> = vec![syllables.iter().map(|s| s.to_string()).collect()];
+        if chars.len() == syllables.len() {
+            let mut changed = syllables.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            for (n, &c) in chars.iter().enumerate() {
+                let next_tone = syllables.get(n + 1).map(|s| tone(s));
+                match (c, syllables[n].to_lowercase().as_str(), next_tone) {
+                    ('一', "yī", Some(4 | 5)) => changed[n] = with_tone(syllables[n], 2),
+                    ('一', "yī", Some(_)) => changed[n] = with_tone(syllables[n], 4),
+                    ('不', "bù", Some(4)) => changed[n] = with_tone(syllables[n], 2),
+                    ('一', "yī", None) if chars.len() == 1 => {
+                        sandhi.push(vec![with_tone(syllables[n], 2)]);
+                        sandhi.push(vec![with_tone(syllables[n], 4)]);
+                    }
+                    ('不', "bù", None) if chars.len() == 1 => {
+                        sandhi.push(vec![with_tone(syllables[n], 2)]);
+                    }
+                    _ => {}
+                }
+            }
+            sandhi.push(changed);
+        }
+
+        let mut variants = sandhi.clone();
+        if self.neutral_tone.contains(word) {
+            for mut syllables in sandhi {
+                if let Some(last) = syllables.last_mut() {
+                    *last = last.chars().map(strip_tone).collect();
+                }
+                variants.push(syllables);
+            }
+        }
+        variants.into_iter().map(|v| v.join(" ")).unique().collect()
+    }
+}
+
+// Tone of a syllable: 1-4, or 5 for the neutral tone.
+fn tone(syllable: &str) -> u8 {
+    const TONE_MARKS: [&str; 4] = [
+        "āēīōūǖĀĒĪŌŪǕ",
+        "áéíóúǘÁÉÍÓÚǗ",
+        "ǎěǐǒǔǚǍĚǏǑǓǙ",
+        "àèìòùǜÀÈÌÒÙǛ",
+    ];
+    syllable
+        .chars()
+        .find_map(|c| TONE_MARKS.iter().position(|marks| marks.contains(c)))
+        .map_or(5, |n| n as u8 + 1)
+}
+
+fn with_tone(syllable: &str, tone: u8) -> String {
+    prettify_pinyin::prettify(&format!("{syllable}{tone}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variants(word: &str, reading: &str) -> Vec<String> {
+        Overrides::default().variants(word, reading)
+    }
+
+    #[test]
+    fn test_tone() {
+        assert_eq!(tone("yī"), 1);
+        assert_eq!(tone("guó"), 2);
+        assert_eq!(tone("nǚ"), 3);
+        assert_eq!(tone("dìng"), 4);
+        assert_eq!(tone("ge"), 5);
+    }
+
+    #[test]
+    fn test_yi_sandhi() {
+        assert_eq!(variants("一定", "yī dìng"), vec!["yī dìng", "yí dìng"]);
+        assert!(variants("一般", "yī bān").contains(&"yì bān".to_string()));
+        assert!(variants("一个", "yī ge").contains(&"yí ge".to_string()));
+        assert_eq!(variants("一", "yī"), vec!["yī", "yí", "yì"]);
+        assert!(!variants("第一", "dì yī").contains(&"dì yí".to_string()));
+    }
+
+    #[test]
+    fn test_bu_sandhi() {
+        assert!(variants("不断", "bù duàn").contains(&"bú duàn".to_string()));
+        assert_eq!(variants("不同", "bù tóng"), vec!["bù tóng"]);
+        assert_eq!(variants("不", "bù"), vec!["bù", "bú"]);
+    }
+
+    #[test]
+    fn test_neutral_tone() {
+        assert_eq!(variants("个", "gè"), vec!["gè", "ge"]);
+        assert_eq!(variants("是", "shì"), vec!["shì"]);
+        assert!(variants("喇叭", "lǎ bā").contains(&"lǎ ba".to_string()));
+        assert_eq!(variants("中国", "zhōng guó"), vec!["zhōng guó"]);
+        assert_eq!(variants("学习", "xué xí"), vec!["xué xí"]);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("readings.yaml");
+        fs::write(
+            &path,
+            "readings:\n  因素: yīn shù\nneutral_tone:\n  - 东西\n",
+        )
+        .unwrap();
+        let overrides = Overrides::load_from_file(&path).unwrap();
+        assert_eq!(
+            overrides.accepted("因素", "yīn sù"),
+            vec!["yīn sù", "yīn shù"]
+        );
+        assert_eq!(
+            overrides.prefixes("因素很多").collect::<Vec<_>>(),
+            vec![("因素", "yīn shù")]
+        );
+        assert_eq!(overrides.accepted("个", "gè"), vec!["gè", "ge"]);
+        assert_eq!(
+            overrides.accepted("东西", "dōng xī"),
+            vec!["dōng xī", "dōng xi"]
+        );
+        assert_eq!(
+            Overrides::default().accepted("东西", "dōng xī"),
+            vec!["dōng xī"]
+        );
+    }
+}
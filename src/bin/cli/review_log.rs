@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::model::{Grade, Skill, UserModel};
+
+/// A single graded answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Review {
+    pub timestamp: DateTime<Utc>,
+    pub word: String,
+    // Reviews logged before skills were tracked separately have no skill. They were graded by
+    // typing pinyin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill: Option<Skill>,
+    // Id of the exercise the word was reviewed in. Older logs have its Chinese text instead.
+    pub exercise: String,
+    // What the user had typed when the answer was accepted.
+    pub input: String,
+    pub grade: Grade,
+    // Time from the word becoming the one to answer until the answer was accepted.
+    pub latency_ms: i64,
+    pub hint_used: bool,
+}
+
+/// Append-only log of every graded answer. Each line of the log is one review in JSON. A crash can
+/// leave the last line half written, so a last line that can't be read is skipped.
+pub struct ReviewLog {
+    path: PathBuf,
+}
+
+impl ReviewLog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The log next to `user_model.yaml` in the default application data directory
+    pub fn open_default() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::new(UserModel::get_data_dir()?.join("reviews.jsonl")))
+    }
+
+    /// Append a review to the end of the log. A half written last line is dropped first, so the
+    /// review doesn't end up on the same line.
+    pub fn append(&self, review: &Review) -> io::Result<()> {
+        let mut line = serde_json::to_string(review)?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        let len = file.metadata()?.len();
+        if len > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                let mut contents = vec![];
+                file.seek(SeekFrom::Start(0))?;
+                file.read_to_end(&mut contents)?;
+                let complete = contents.iter().rposition(|&byte| byte == b'\n');
+                file.set_len(complete.map_or(0, |end| end as u64 + 1))?;
+            }
+        }
+        file.write_all(line.as_bytes())
+    }
+
+    /// Load all reviews, oldest first. A missing log has no reviews.
+    pub fn load(&self) -> Result<Vec<Review>, Box<dyn std::error::Error>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        Self::load_from_reader(fs::File::open(&self.path)?)
+    }
+
+    /// Load reviews from a reader (generic over any Read type)
+    pub fn load_from_reader<R: Read>(reader: R) -> Result<Vec<Review>, Box<dyn std::error::Error>> {
+        let mut reviews = vec![];
+        let mut lines = BufReader::new(reader).lines().peekable();
+        while let Some(line) = lines.next() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(review) => reviews.push(review),
+                Err(_) if lines.peek().is_none() => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(reviews)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(word: &str, grade: Grade) -> Review {
+        Review {
+            timestamp: DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            word: word.to_string(),
+            skill: Some(Skill::Tones),
+            exercise: "我是学生。".to_string(),
+            input: "wǒ".to_string(),
+            grade,
+            latency_ms: 1200,
+            hint_used: false,
+        }
+    }
+
+    #[test]
+    fn test_append_and_load() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let log = ReviewLog::new(dir.path().join("reviews.jsonl"));
+        assert_eq!(log.load().unwrap(), vec![]);
+
+        let reviews = vec![review("我", Grade::Perfect), review("是", Grade::WrongTone)];
+        for review in &reviews {
+            log.append(review).expect("Failed to append");
+        }
+        assert_eq!(log.load().unwrap(), reviews);
+    }
+
+    #[test]
+    fn test_load_empty_file() {
+        assert_eq!(ReviewLog::load_from_reader("".as_bytes()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_half_written_review_is_skipped() {
+        let line = serde_json::to_string(&review("我", Grade::Perfect)).unwrap();
+        let log = format!("{line}\n{}", &line[..20]);
+        assert_eq!(
+            ReviewLog::load_from_reader(log.as_bytes()).unwrap(),
+            vec![review("我", Grade::Perfect)]
+        );
+        // Only the line being written when a crash happened can be broken.
+        let log = format!("{}\n{line}\n", &line[..20]);
+        assert!(ReviewLog::load_from_reader(log.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_append_after_half_written_review() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("reviews.jsonl");
+        let line = serde_json::to_string(&review("我", Grade::Perfect)).unwrap();
+        fs::write(&path, format!("{line}\n{}", &line[..20])).unwrap();
+
+        let log = ReviewLog::new(&path);
+        log.append(&review("是", Grade::WrongTone))
+            .expect("Failed to append");
+        assert_eq!(
+            log.load().unwrap(),
+            vec![review("我", Grade::Perfect), review("是", Grade::WrongTone)]
+        );
+    }
+}
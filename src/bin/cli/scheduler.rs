@@ -0,0 +1,526 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{Grade, Proficiency};
+
+// Memory strength of a word that has just been introduced or forgotten.
+pub const INITIAL_STRENGTH_SECS: i64 = 5;
+
+// Decides when a word should be reviewed next.
+//
+// Every scheduler keeps its own state inside `Proficiency`. All schedulers are updated on every
+// answer so switching between them does not lose any history; only the active scheduler decides
+// when a word is due.
+pub trait Scheduler {
+    // When the word should be reviewed next.
+    fn due_date(&self, prof: &Proficiency) -> DateTime<Utc>;
+
+    // Update this scheduler's state after the word was answered with the given grade.
+    fn review(&self, prof: &mut Proficiency, grade: Grade, at: DateTime<Utc>);
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum SchedulerKind {
+    #[default]
+    Classic,
+    Sm2,
+    Fsrs,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedulers {
+    pub active: SchedulerKind,
+    pub classic: Classic,
+    pub sm2: Sm2,
+    pub fsrs: Fsrs,
+}
+
+impl Schedulers {
+    pub fn new(active: SchedulerKind) -> Self {
+        Self {
+            active,
+            ..Default::default()
+        }
+    }
+
+    pub fn active(&self) -> &dyn Scheduler {
+        match self.active {
+            SchedulerKind::Classic => &self.classic,
+            SchedulerKind::Sm2 => &self.sm2,
+            SchedulerKind::Fsrs => &self.fsrs,
+        }
+    }
+
+    /// Every scheduler, in the order they review an answer. SM-2 and FSRS seed their state from
+    /// the classic one, so classic comes last to seed them from its state before the answer.
+    pub fn all(&self) -> [&dyn Scheduler; 3] {
+        [&self.sm2, &self.fsrs, &self.classic]
+    }
+}
+
+/// Tunable constants of the classic scheduler. `optimize` fits them to the review log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassicParams {
+    // Memory strength of a new or forgotten word.
+    pub initial_strength_secs: f64,
+    // Relative growth of a perfect answer before the target date.
+    pub early_growth: f64,
+    // Relative growth of a perfect answer after the target date.
+    pub late_growth: f64,
+}
+
+impl Default for ClassicParams {
+    fn default() -> Self {
+        ClassicParams {
+            initial_strength_secs: INITIAL_STRENGTH_SECS as f64,
+            early_growth: 1.0 / 50.0,
+            late_growth: 4.0,
+        }
+    }
+}
+
+// The original erudify rule. The state is `Proficiency::memory_strength` and
+// `Proficiency::target_date`. With the default parameters:
+//
+//  - Perfect: +2% if before target date, x5 if after target date.
+//  - Typo: +1% if before target date, x3 if after target date.
+//  - Slow: +0% if before target date, x2 if after target date.
+//  - WrongTone: +0% if before target date, x1.5 if after target date.
+//  - Hinted: +0%. The word is shown again after the current memory strength.
+//  - Wrong: reset memory strength to its initial value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Classic {
+    pub params: ClassicParams,
+}
+
+impl Classic {
+    // Recall probability at the target date. The forgetting curve is 0.9^(elapsed/strength).
+    const TARGET_RETENTION: f64 = 0.9;
+
+    pub fn new(params: ClassicParams) -> Self {
+        Classic { params }
+    }
+
+    pub fn initial_strength(&self) -> Duration {
+        Duration::milliseconds((self.params.initial_strength_secs * 1000.0).round() as i64)
+    }
+
+    // Predicted probability of recalling the word at `at`.
+    pub fn recall_probability(&self, prof: &Proficiency, at: DateTime<Utc>) -> f64 {
+        let last_review = prof.target_date - prof.memory_strength;
+        let elapsed = (at - last_review).num_milliseconds().max(0) as f64;
+        let strength = prof.memory_strength.num_milliseconds().max(1) as f64;
+        Self::TARGET_RETENTION.powf(elapsed / strength)
+    }
+}
+
+impl Scheduler for Classic {
+    fn due_date(&self, prof: &Proficiency) -> DateTime<Utc> {
+        prof.target_date
+    }
+
+    fn review(&self, prof: &mut Proficiency, grade: Grade, at: DateTime<Utc>) {
+        let before_target = prof.target_date > at;
+        let ClassicParams {
+            early_growth,
+            late_growth,
+            ..
+        } = self.params;
+        let growth = match grade {
+            Grade::Perfect if before_target => early_growth,
+            Grade::Perfect => late_growth,
+            Grade::Typo if before_target => early_growth / 2.0,
+            Grade::Typo => late_growth / 2.0,
+            Grade::Slow if !before_target => late_growth / 4.0,
+            Grade::WrongTone if !before_target => late_growth / 8.0,
+            Grade::Slow | Grade::WrongTone | Grade::Hinted => 0.0,
+            Grade::Wrong => {
+                prof.memory_strength = self.initial_strength();
+                prof.target_date = at + prof.memory_strength;
+                return;
+            }
+        };
+        let strength = prof.memory_strength;
+        prof.memory_strength = strength
+            + Duration::milliseconds((strength.num_milliseconds() as f64 * growth).round() as i64);
+        prof.target_date = at + prof.memory_strength;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sm2State {
+    repetitions: u32,
+    interval: Duration,
+    ease: f64,
+    due: DateTime<Utc>,
+}
+
+impl Sm2State {
+    // Words reviewed before SM-2 was available only have the classic state. Treat their memory
+    // strength as the current SM-2 interval.
+    fn from_classic(prof: &Proficiency) -> Self {
+        Sm2State {
+            repetitions: if prof.memory_strength >= Duration::days(1) {
+                2
+            } else {
+                0
+            },
+            interval: prof.memory_strength,
+            ease: Sm2::INITIAL_EASE,
+            due: prof.target_date,
+        }
+    }
+}
+
+// SuperMemo 2. Grades are mapped to SM-2 quality scores (0-5). Failed words are shown again after
+// a few seconds rather than the next day so they are drilled within the same session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sm2;
+
+impl Sm2 {
+    const INITIAL_EASE: f64 = 2.5;
+    const MIN_EASE: f64 = 1.3;
+
+    fn quality(grade: Grade) -> u32 {
+        match grade {
+            Grade::Perfect => 5,
+            Grade::Slow | Grade::Typo => 4,
+            Grade::WrongTone => 3,
+            Grade::Hinted => 1,
+            Grade::Wrong => 0,
+        }
+    }
+}
+
+impl Scheduler for Sm2 {
+    fn due_date(&self, prof: &Proficiency) -> DateTime<Utc> {
+        prof.sm2
+            .as_ref()
+            .map_or(prof.target_date, |state| state.due)
+    }
+
+    fn review(&self, prof: &mut Proficiency, grade: Grade, at: DateTime<Utc>) {
+        let mut state = prof
+            .sm2
+            .take()
+            .unwrap_or_else(|| Sm2State::from_classic(prof));
+        let q = Self::quality(grade);
+        if q < 3 {
+            state.repetitions = 0;
+            state.interval = Duration::seconds(INITIAL_STRENGTH_SECS);
+        } else {
+            state.interval = match state.repetitions {
+                0 => Duration::days(1),
+                1 => Duration::days(6),
+                _ => Duration::seconds(
+                    (state.interval.num_seconds() as f64 * state.ease).round() as i64
+                ),
+            };
+            state.repetitions += 1;
+        }
+        let miss = (5 - q) as f64;
+        state.ease = (state.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(Self::MIN_EASE);
+        state.due = at + state.interval;
+        prof.sm2 = Some(state);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FsrsState {
+    // Days until recall probability drops to 90%.
+    stability: f64,
+    // 1 (easy) to 10 (hard).
+    difficulty: f64,
+    last_review: DateTime<Utc>,
+    due: DateTime<Utc>,
+}
+
+// Free Spaced Repetition Scheduler (FSRS-4.5) with the published default weights. Grades are
+// mapped to the FSRS ratings Again (1), Hard (2) and Good (3). Like the other schedulers, words
+// rated Again are shown again after a few seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fsrs {
+    pub weights: [f64; 17],
+    pub desired_retention: f64,
+}
+
+impl Default for Fsrs {
+    fn default() -> Self {
+        Fsrs {
+            weights: [
+                0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474, 0.1367,
+                1.0461, 2.1072, 0.0793, 0.3246, 1.587, 0.2272, 2.8755,
+            ],
+            desired_retention: 0.9,
+        }
+    }
+}
+
+impl Fsrs {
+    const DECAY: f64 = -0.5;
+    const FACTOR: f64 = 19.0 / 81.0;
+
+    fn rating(grade: Grade) -> u32 {
+        match grade {
+            Grade::Perfect => 3,
+            Grade::Slow | Grade::Typo | Grade::WrongTone => 2,
+            Grade::Hinted | Grade::Wrong => 1,
+        }
+    }
+
+    fn retrievability(elapsed_days: f64, stability: f64) -> f64 {
+        (1.0 + Self::FACTOR * elapsed_days / stability).powf(Self::DECAY)
+    }
+
+    fn interval_days(&self, stability: f64) -> f64 {
+        stability / Self::FACTOR * (self.desired_retention.powf(1.0 / Self::DECAY) - 1.0)
+    }
+
+    fn initial_difficulty(&self, rating: u32) -> f64 {
+        let w = &self.weights;
+        (w[4] - (rating as f64 - 3.0) * w[5]).clamp(1.0, 10.0)
+    }
+
+    fn initial_state(&self, rating: u32, at: DateTime<Utc>) -> FsrsState {
+        FsrsState {
+            stability: self.weights[rating as usize - 1],
+            difficulty: self.initial_difficulty(rating),
+            last_review: at,
+            due: at,
+        }
+    }
+
+    // Words reviewed before FSRS was available only have the classic state. Treat their memory
+    // strength as the current stability. Words that were just introduced start from scratch.
+    fn seed_from_classic(&self, prof: &Proficiency) -> Option<FsrsState> {
+        if prof.memory_strength <= Duration::seconds(INITIAL_STRENGTH_SECS) {
+            return None;
+        }
+        Some(FsrsState {
+            stability: days(prof.memory_strength),
+            difficulty: self.initial_difficulty(3),
+            last_review: prof.target_date - prof.memory_strength,
+            due: prof.target_date,
+        })
+    }
+
+    fn next_state(&self, state: &FsrsState, rating: u32, at: DateTime<Utc>) -> FsrsState {
+        let w = &self.weights;
+        let elapsed = days(at - state.last_review).max(0.0);
+        let r = Self::retrievability(elapsed, state.stability);
+        let d = state.difficulty;
+        let stability = if rating == 1 {
+            w[11]
+                * d.powf(-w[12])
+                * ((state.stability + 1.0).powf(w[13]) - 1.0)
+                * (w[14] * (1.0 - r)).exp()
+        } else {
+            let hard_penalty = if rating == 2 { w[15] } else { 1.0 };
+            state.stability
+                * (w[8].exp()
+                    * (11.0 - d)
+                    * state.stability.powf(-w[9])
+                    * ((w[10] * (1.0 - r)).exp() - 1.0)
+                    * hard_penalty
+                    + 1.0)
+        };
+        let difficulty = d - w[6] * (rating as f64 - 3.0);
+        let difficulty =
+            (w[7] * self.initial_difficulty(3) + (1.0 - w[7]) * difficulty).clamp(1.0, 10.0);
+        FsrsState {
+            stability,
+            difficulty,
+            last_review: at,
+            due: at,
+        }
+    }
+}
+
+impl Scheduler for Fsrs {
+    fn due_date(&self, prof: &Proficiency) -> DateTime<Utc> {
+        prof.fsrs
+            .as_ref()
+            .map_or(prof.target_date, |state| state.due)
+    }
+
+    fn review(&self, prof: &mut Proficiency, grade: Grade, at: DateTime<Utc>) {
+        let rating = Self::rating(grade);
+        let mut state = match prof.fsrs.take().or_else(|| self.seed_from_classic(prof)) {
+            Some(state) => self.next_state(&state, rating, at),
+            None => self.initial_state(rating, at),
+        };
+        state.due = if rating == 1 {
+            at + Duration::seconds(INITIAL_STRENGTH_SECS)
+        } else {
+            at + Duration::seconds((self.interval_days(state.stability) * 86400.0).round() as i64)
+        };
+        prof.fsrs = Some(state);
+    }
+}
+
+fn days(duration: Duration) -> f64 {
+    duration.num_seconds() as f64 / 86400.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn proficiency(memory_strength: Duration, target_date: DateTime<Utc>) -> Proficiency {
+        Proficiency {
+            target_date,
+            memory_strength,
+            sm2: None,
+            fsrs: None,
+        }
+    }
+
+    fn new_word() -> Proficiency {
+        proficiency(Duration::seconds(INITIAL_STRENGTH_SECS), now())
+    }
+
+    #[test]
+    fn test_classic_perfect() {
+        let mut prof = proficiency(Duration::days(10), now() - Duration::hours(1));
+        Classic::default().review(&mut prof, Grade::Perfect, now());
+        assert_eq!(prof.memory_strength, Duration::days(50));
+        assert_eq!(
+            Classic::default().due_date(&prof),
+            now() + Duration::days(50)
+        );
+
+        // Reviewing before the target date barely increases the strength.
+        let mut prof = proficiency(Duration::days(50), now() + Duration::hours(1));
+        Classic::default().review(&mut prof, Grade::Perfect, now());
+        assert_eq!(prof.memory_strength, Duration::days(51));
+    }
+
+    #[test]
+    fn test_classic_wrong_resets_strength() {
+        let mut prof = proficiency(Duration::days(10), now() - Duration::hours(1));
+        Classic::default().review(&mut prof, Grade::Wrong, now());
+        assert_eq!(
+            prof.memory_strength,
+            Duration::seconds(INITIAL_STRENGTH_SECS)
+        );
+        assert_eq!(
+            prof.target_date,
+            now() + Duration::seconds(INITIAL_STRENGTH_SECS)
+        );
+    }
+
+    #[test]
+    fn test_classic_wrong_tone_keeps_the_interval() {
+        let mut prof = proficiency(Duration::days(60), now() - Duration::hours(1));
+        Classic::default().review(&mut prof, Grade::WrongTone, now());
+        assert_eq!(prof.memory_strength, Duration::days(90));
+        assert_eq!(prof.target_date, now() + Duration::days(90));
+
+        let mut prof = proficiency(Duration::days(60), now() + Duration::hours(1));
+        Classic::default().review(&mut prof, Grade::WrongTone, now());
+        assert_eq!(prof.memory_strength, Duration::days(60));
+    }
+
+    #[test]
+    fn test_classic_hinted_keeps_strength() {
+        let mut prof = proficiency(Duration::days(10), now() - Duration::hours(1));
+        Classic::default().review(&mut prof, Grade::Hinted, now());
+        assert_eq!(prof.memory_strength, Duration::days(10));
+        assert_eq!(prof.target_date, now() + Duration::days(10));
+    }
+
+    // Better grades must never lead to an earlier due date.
+    fn assert_grades_ordered(scheduler: &dyn Scheduler, prof: &Proficiency, at: DateTime<Utc>) {
+        let grades = [
+            Grade::Perfect,
+            Grade::Typo,
+            Grade::Slow,
+            Grade::WrongTone,
+            Grade::Hinted,
+            Grade::Wrong,
+        ];
+        // The schedulers must agree with the order `max` keeps the worst grade by.
+        assert!(grades.windows(2).all(|w| w[0] < w[1]));
+        let due_dates = grades.map(|grade| {
+            let mut prof = prof.clone();
+            scheduler.review(&mut prof, grade, at);
+            scheduler.due_date(&prof)
+        });
+        assert!(due_dates.windows(2).all(|w| w[0] >= w[1]), "{due_dates:?}");
+    }
+
+    #[test]
+    fn test_grades_are_ordered() {
+        let due = proficiency(Duration::days(10), now() - Duration::hours(1));
+        let schedulers = Schedulers::default();
+        for scheduler in schedulers.all() {
+            assert_grades_ordered(scheduler, &new_word(), now());
+            assert_grades_ordered(scheduler, &due, now());
+        }
+    }
+
+    #[test]
+    fn test_sm2_intervals() {
+        let mut prof = new_word();
+        Sm2.review(&mut prof, Grade::Perfect, now());
+        assert_eq!(Sm2.due_date(&prof), now() + Duration::days(1));
+        Sm2.review(&mut prof, Grade::Perfect, now() + Duration::days(1));
+        assert_eq!(Sm2.due_date(&prof), now() + Duration::days(7));
+        Sm2.review(&mut prof, Grade::Perfect, now() + Duration::days(7));
+        let interval = Sm2.due_date(&prof) - (now() + Duration::days(7));
+        // Ease grows from 2.5 to 2.7 after two perfect answers.
+        assert_eq!(
+            interval,
+            Duration::seconds((6.0 * 86400.0 * 2.7_f64).round() as i64)
+        );
+
+        Sm2.review(&mut prof, Grade::Wrong, now() + Duration::days(30));
+        assert_eq!(
+            Sm2.due_date(&prof),
+            now() + Duration::days(30) + Duration::seconds(INITIAL_STRENGTH_SECS)
+        );
+    }
+
+    #[test]
+    fn test_fsrs_intervals_grow() {
+        let fsrs = Fsrs::default();
+        let mut prof = new_word();
+        let mut at = now();
+        let mut last_interval = Duration::zero();
+        for _ in 0..5 {
+            fsrs.review(&mut prof, Grade::Perfect, at);
+            let interval = fsrs.due_date(&prof) - at;
+            assert!(interval > last_interval);
+            last_interval = interval;
+            at = fsrs.due_date(&prof);
+        }
+        // First interval follows the initial stability for 'Good'.
+        let mut prof = new_word();
+        fsrs.review(&mut prof, Grade::Perfect, now());
+        assert_eq!(
+            fsrs.due_date(&prof) - now(),
+            Duration::seconds((fsrs.weights[2] * 86400.0).round() as i64)
+        );
+    }
+
+    #[test]
+    fn test_unreviewed_state_falls_back_to_classic() {
+        let prof = proficiency(Duration::days(10), now() + Duration::days(3));
+        assert_eq!(Sm2.due_date(&prof), now() + Duration::days(3));
+        assert_eq!(Fsrs::default().due_date(&prof), now() + Duration::days(3));
+
+        // Existing memory strength is carried over instead of starting from scratch.
+        let mut sm2 = prof.clone();
+        Sm2.review(&mut sm2, Grade::Perfect, now() + Duration::days(3));
+        assert!(Sm2.due_date(&sm2) > now() + Duration::days(20));
+        let mut fsrs = prof.clone();
+        Fsrs::default().review(&mut fsrs, Grade::Perfect, now() + Duration::days(3));
+        assert!(Fsrs::default().due_date(&fsrs) > now() + Duration::days(13));
+    }
+}
@@ -0,0 +1,377 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::convert::Exercise;
+use crate::index::ExerciseIndex;
+use crate::model::{Skill, UserModel, WordListStatus};
+use crate::review_log::Review;
+
+/// Keeps the progress through a word list up to date as words are reviewed and exercises are
+/// seen, instead of counting it again from every word and exercise. Each update only looks at the
+/// word or exercise that changed; words that become due as time passes are counted when the status
+/// is next asked for.
+pub struct StatusTracker {
+    skills: Vec<Skill>,
+    total_words: usize,
+    // Seen word of the word list -> when it is next due.
+    due_dates: HashMap<String, DateTime<Utc>>,
+    // Words that are due, and words that are due within the hour.
+    due: DueCount,
+    due_soon: DueCount,
+    // Every seen word, whether in the word list or not.
+    seen_words: HashSet<String>,
+    // Number of words of each exercise that haven't been seen yet.
+    unseen: Vec<usize>,
+    // Id of each unlocked exercise -> when its last word was first seen.
+    unlocked: HashMap<String, DateTime<Utc>>,
+    seen_sentences: HashSet<String>,
+    // Day the count of exercises unlocked today is for.
+    today: NaiveDate,
+    unlocked_today: usize,
+}
+
+// Number of words due by a time that only moves forward. Due dates are kept in a heap and words
+// are counted once their due date has passed. Entries left by words that were reviewed again are
+// skipped.
+struct DueCount {
+    // How far ahead of the current time words are counted.
+    ahead: Duration,
+    heap: BinaryHeap<Reverse<(DateTime<Utc>, String)>>,
+    passed: HashSet<String>,
+}
+
+impl DueCount {
+    fn new(ahead: Duration) -> Self {
+        DueCount {
+            ahead,
+            heap: BinaryHeap::new(),
+            passed: HashSet::new(),
+        }
+    }
+
+    fn update(&mut self, word: &str, due: DateTime<Utc>) {
+        self.passed.remove(word);
+        self.heap.push(Reverse((due, word.to_string())));
+    }
+
+    fn count(&mut self, at: DateTime<Utc>, due_dates: &HashMap<String, DateTime<Utc>>) -> usize {
+        while let Some(Reverse((due, _))) = self.heap.peek() {
+            if *due > at + self.ahead {
+                break;
+            }
+            let Reverse((due, word)) = self.heap.pop().unwrap();
+            if due_dates.get(&word) == Some(&due) {
+                self.passed.insert(word);
+            }
+        }
+        self.passed.len()
+    }
+}
+
+impl StatusTracker {
+    /// Start tracking the progress of `model` through the index's word list, counting proficiency
+    /// in the given skills only. The reviews tell when words were first seen, to count exercises
+    /// unlocked today.
+    pub fn new(
+        model: &UserModel,
+        index: &ExerciseIndex,
+        skills: &[Skill],
+        reviews: &[Review],
+        at: DateTime<Utc>,
+    ) -> Self {
+        let mut tracker = StatusTracker {
+            skills: skills.to_vec(),
+            total_words: index.word_list().len(),
+            due_dates: HashMap::new(),
+            due: DueCount::new(Duration::zero()),
+            due_soon: DueCount::new(Duration::hours(1)),
+            seen_words: HashSet::new(),
+            unseen: (0..index.exercises().len())
+                .map(|n| index.words(n).iter().collect::<HashSet<_>>().len())
+                .collect(),
+            unlocked: HashMap::new(),
+            seen_sentences: HashSet::new(),
+            today: at.with_timezone(&Local).date_naive(),
+            unlocked_today: 0,
+        };
+
+        let mut first_seen: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        for review in reviews {
+            // Reviews logged before skills were tracked separately were of the pinyin skills.
+            let skills = match &review.skill {
+                Some(skill) => std::slice::from_ref(skill),
+                None => &Skill::PINYIN[..],
+            };
+            if skills.iter().any(|skill| tracker.skills.contains(skill)) {
+                let first = first_seen.entry(&review.word).or_insert(review.timestamp);
+                *first = (*first).min(review.timestamp);
+            }
+        }
+        // Words are taken in the order they were first seen, so exercises are unlocked by their
+        // last word. Words seen before the review log was kept were seen at some unknown earlier
+        // time.
+        let words = index
+            .word_list()
+            .iter()
+            .chain((0..index.exercises().len()).flat_map(|n| index.words(n)))
+            .unique()
+            .map(|word| {
+                let seen_at = first_seen.get(word.as_str()).copied();
+                (seen_at.unwrap_or_default(), word)
+            })
+            .sorted();
+        for (seen_at, word) in words {
+            tracker.reviewed(model, index, word, seen_at);
+        }
+        tracker
+    }
+
+    /// Update the status after `word` was reviewed at `at`.
+    pub fn reviewed(
+        &mut self,
+        model: &UserModel,
+        index: &ExerciseIndex,
+        word: &str,
+        at: DateTime<Utc>,
+    ) {
+        let Some(due) = model.due_date(word, &self.skills) else {
+            return;
+        };
+        if index.in_list(word) {
+            self.due_dates.insert(word.to_string(), due);
+            self.due.update(word, due);
+            self.due_soon.update(word, due);
+        }
+        if !self.seen_words.insert(word.to_string()) {
+            return;
+        }
+        for &n in index.with_word(word) {
+            self.unseen[n] -= 1;
+            if self.unseen[n] > 0 || !index.words(n).iter().any(|word| index.in_list(word)) {
+                continue;
+            }
+            let id = index.exercises()[n].id();
+            if self.unlocked.contains_key(&id) {
+                continue;
+            }
+            self.unlocked.insert(id.clone(), at);
+            if at.with_timezone(&Local).date_naive() == self.today {
+                self.unlocked_today += 1;
+            }
+            if model.seen_exercise(&index.exercises()[n]) {
+                self.seen_sentences.insert(id);
+            }
+        }
+    }
+
+    /// Update the status after an exercise was seen.
+    pub fn seen(&mut self, exercise: &Exercise) {
+        let id = exercise.id();
+        if self.unlocked.contains_key(&id) {
+            self.seen_sentences.insert(id);
+        }
+    }
+
+    /// The status at `at`, which must not be before the time of the last call.
+    pub fn status(&mut self, at: DateTime<Utc>) -> WordListStatus {
+        let today = at.with_timezone(&Local).date_naive();
+        if today != self.today {
+            self.today = today;
+            self.unlocked_today = self
+                .unlocked
+                .values()
+                .filter(|unlocked| unlocked.with_timezone(&Local).date_naive() == today)
+                .count();
+        }
+        let words_to_review = self.due.count(at, &self.due_dates);
+        let due_by_next_hour = self.due_soon.count(at, &self.due_dates);
+        WordListStatus {
+            total_words: self.total_words,
+            known_words: self.due_dates.len() - words_to_review,
+            words_to_review,
+            words_due_soon: due_by_next_hour - words_to_review,
+            seen_sentences: self.seen_sentences.len(),
+            unlocked_sentences: self.unlocked.len(),
+            unlocked_today: self.unlocked_today,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Grade;
+    use chrono::TimeZone;
+    use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap()
+    }
+
+    fn review(model: &mut UserModel, word: &str, grade: Grade, at: DateTime<Utc>) {
+        for skill in Skill::ALL {
+            model.review(word, skill, grade, at);
+        }
+    }
+
+    #[test]
+    fn test_status_follows_reviews() {
+        let index = ExerciseIndex::new(
+            vec![
+                Exercise::from_words(&["我", "是"]),
+                Exercise::from_words(&["你", "是"]),
+                Exercise::from_words(&["他", "很", "好"]),
+            ],
+            vec!["我".to_string(), "是".to_string(), "你".to_string()],
+        );
+        let mut model = UserModel::new();
+        review(&mut model, "我", Grade::Perfect, now() - Duration::days(3));
+        model.mark_seen(&index.exercises()[0], now() - Duration::days(3));
+
+        let mut tracker = StatusTracker::new(&model, &index, &Skill::ALL, &[], now());
+        let status = tracker.status(now());
+        assert_eq!(status.total_words, 3);
+        assert_eq!(status.known_words + status.words_to_review, 1);
+        assert_eq!(status.unlocked_sentences, 0);
+
+        review(&mut model, "是", Grade::Perfect, now());
+        tracker.reviewed(&model, &index, "是", now());
+        let status = tracker.status(now());
+        assert_eq!(status.known_words + status.words_to_review, 2);
+        assert_eq!(status.unlocked_sentences, 1);
+        assert_eq!(status.unlocked_today, 1);
+        // The exercise was seen before it was unlocked.
+        assert_eq!(status.seen_sentences, 1);
+
+        review(&mut model, "你", Grade::Wrong, now());
+        tracker.reviewed(&model, &index, "你", now());
+        model.mark_seen(&index.exercises()[1], now());
+        tracker.seen(&index.exercises()[1]);
+        let status = tracker.status(now());
+        assert_eq!(status.unlocked_sentences, 2);
+        assert_eq!(status.seen_sentences, 2);
+
+        // Words outside the word list unlock nothing on their own.
+        for word in ["他", "很", "好"] {
+            review(&mut model, word, Grade::Perfect, now());
+            tracker.reviewed(&model, &index, word, now());
+        }
+        assert_eq!(tracker.status(now()).unlocked_sentences, 2);
+    }
+
+    #[test]
+    fn test_words_become_due() {
+        let index = ExerciseIndex::new(vec![Exercise::from_words(&["我"])], vec!["我".to_string()]);
+        let mut model = UserModel::new();
+        let due = now() + Duration::days(1);
+        model.set_target_date("我", due);
+        let mut tracker = StatusTracker::new(&model, &index, &Skill::ALL, &[], now());
+
+        let status = tracker.status(now());
+        assert_eq!((status.known_words, status.words_to_review), (1, 0));
+        assert_eq!(status.words_due_soon, 0);
+        let status = tracker.status(due - Duration::minutes(30));
+        assert_eq!((status.known_words, status.words_to_review), (1, 0));
+        assert_eq!(status.words_due_soon, 1);
+        let status = tracker.status(due);
+        assert_eq!((status.known_words, status.words_to_review), (0, 1));
+        assert_eq!(status.words_due_soon, 0);
+
+        // Reviewing the word again schedules it in the future.
+        model.set_target_date("我", due + Duration::days(2));
+        tracker.reviewed(&model, &index, "我", due);
+        let status = tracker.status(due);
+        assert_eq!((status.known_words, status.words_to_review), (1, 0));
+    }
+
+    #[test]
+    fn test_unlocked_today_uses_review_log() {
+        let index = ExerciseIndex::new(vec![Exercise::from_words(&["我"])], vec!["我".to_string()]);
+        let mut model = UserModel::new();
+        let yesterday = now() - Duration::days(1);
+        review(&mut model, "我", Grade::Perfect, yesterday);
+        let reviews = vec![Review {
+            timestamp: yesterday,
+            word: "我".to_string(),
+            skill: Some(Skill::Syllables),
+            exercise: "我".to_string(),
+            input: "wo".to_string(),
+            grade: Grade::Perfect,
+            latency_ms: 1000,
+            hint_used: false,
+        }];
+        let mut tracker = StatusTracker::new(&model, &index, &Skill::ALL, &reviews, yesterday);
+        assert_eq!(tracker.status(yesterday).unlocked_today, 1);
+        assert_eq!(tracker.status(now()).unlocked_today, 0);
+    }
+
+    #[test]
+    fn test_tracker_matches_full_scan() {
+        let words = [
+            "我", "你", "他", "是", "很", "好", "人", "大", "小", "学生", "老师", "朋友",
+        ];
+        let grades = [
+            Grade::Perfect,
+            Grade::Typo,
+            Grade::Slow,
+            Grade::WrongTone,
+            Grade::Hinted,
+            Grade::Wrong,
+        ];
+        let mut rng = SmallRng::seed_from_u64(0);
+        let exercises = (0..20)
+            .map(|_| {
+                let n = rng.gen_range(1..=3);
+                Exercise::from_words(&words.choose_multiple(&mut rng, n).copied().collect_vec())
+            })
+            .collect();
+        let word_list = words[..8].iter().map(|word| word.to_string()).collect();
+        let index = ExerciseIndex::new(exercises, word_list);
+        // Reviews of the other skills must not count.
+        let skills = Skill::PINYIN;
+        let mut model = UserModel::new();
+        let mut reviews = vec![];
+        let mut at = now();
+        let mut tracker = StatusTracker::new(&model, &index, &skills, &reviews, at);
+
+        for _ in 0..400 {
+            match rng.gen_range(0..4) {
+                0 | 1 => {
+                    let word = *words.choose(&mut rng).unwrap();
+                    let skill = *Skill::ALL.choose(&mut rng).unwrap();
+                    let grade = *grades.choose(&mut rng).unwrap();
+                    model.review(word, skill, grade, at);
+                    tracker.reviewed(&model, &index, word, at);
+                    reviews.push(Review {
+                        timestamp: at,
+                        word: word.to_string(),
+                        skill: Some(skill),
+                        exercise: String::new(),
+                        input: String::new(),
+                        grade,
+                        latency_ms: 0,
+                        hint_used: grade == Grade::Hinted,
+                    });
+                }
+                2 => {
+                    let exercise = index.exercises().choose(&mut rng).unwrap();
+                    model.mark_seen(exercise, at);
+                    tracker.seen(exercise);
+                }
+                _ => at += Duration::minutes(rng.gen_range(0..12 * 60)),
+            }
+            assert_eq!(
+                tracker.status(at),
+                model.status(&index, &reviews, at, &skills)
+            );
+        }
+        // The tracker started from the review log agrees too.
+        let mut tracker = StatusTracker::new(&model, &index, &skills, &reviews, at);
+        assert_eq!(
+            tracker.status(at),
+            model.status(&index, &reviews, at, &skills)
+        );
+    }
+}
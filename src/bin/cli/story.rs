@@ -0,0 +1,130 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::model::{Grade, Skill, UserModel};
+
+/// An exercise file read in order as one text.
+pub struct Story {
+    // Key of the story in the bookmarks.
+    pub name: String,
+    // Index of the exercise being read.
+    pub position: usize,
+    // Words remembered for at least this long are shown without pinyin.
+    pub known_strength: Duration,
+}
+
+impl Story {
+    /// Whether the pinyin of a word is shown next to it, because the word isn't known yet.
+    pub fn shows_reading(&self, model: &UserModel, word: &str, skills: &[Skill]) -> bool {
+        !model
+            .memory_strength(word, skills)
+            .is_some_and(|strength| strength >= self.known_strength)
+    }
+}
+
+/// Grade of an answer typed while its reading was shown. Copying the reading is weaker than
+/// recalling it, but still a success, so shown words are learned and stop being shown.
+pub fn shown_reading_grade(grade: Grade) -> Grade {
+    grade.max(Grade::Slow)
+}
+
+/// Where the user stopped reading each story.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Bookmarks {
+    // Story name -> index of the first exercise not yet read.
+    #[serde(default)]
+    pub positions: BTreeMap<String, usize>,
+}
+
+impl Bookmarks {
+    /// Load Bookmarks from a YAML file
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = fs::File::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// Save Bookmarks to a YAML file
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::File::create(path)?;
+        serde_yaml::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Load Bookmarks from the default application data directory. A missing file has no
+    /// bookmarks.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = UserModel::get_data_dir()?.join("bookmarks.yaml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load_from_file(path)
+    }
+
+    /// Save Bookmarks to the default application data directory
+    pub fn store(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to_file(UserModel::get_data_dir()?.join("bookmarks.yaml"))
+    }
+
+    /// Remember the position in a story. A finished story starts over next time.
+    pub fn set(&mut self, name: &str, position: usize, len: usize) {
+        if position >= len {
+            self.positions.remove(name);
+        } else {
+            self.positions.insert(name.to_string(), position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_save_file() {
+        let temp_file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set("stories/three-pigs.yaml", 3, 10);
+        bookmarks
+            .save_to_file(temp_file.path())
+            .expect("Failed to save");
+        assert_eq!(
+            Bookmarks::load_from_file(temp_file.path()).unwrap(),
+            bookmarks
+        );
+    }
+
+    #[test]
+    fn test_shown_readings_are_learned() {
+        let story = Story {
+            name: "story.yaml".to_string(),
+            position: 0,
+            known_strength: Duration::days(7),
+        };
+        let mut model = UserModel::new();
+        let mut at = chrono::DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        // The word is met again whenever it is due, and answered by copying its reading.
+        let mut times_shown = 0;
+        while story.shows_reading(&model, "你好", &Skill::PINYIN) {
+            assert!(times_shown < 100, "the reading is always shown");
+            for skill in Skill::PINYIN {
+                model.review("你好", skill, shown_reading_grade(Grade::Perfect), at);
+            }
+            at = model.due_date("你好", &Skill::PINYIN).unwrap();
+            times_shown += 1;
+        }
+    }
+
+    #[test]
+    fn test_finished_story_starts_over() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set("story.yaml", 9, 10);
+        assert_eq!(bookmarks.positions.get("story.yaml"), Some(&9));
+        bookmarks.set("story.yaml", 10, 10);
+        assert_eq!(bookmarks.positions.get("story.yaml"), None);
+    }
+}
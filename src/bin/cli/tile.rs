@@ -0,0 +1,854 @@
+use chrono::Utc;
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::convert::Exercise;
+use crate::index::ExerciseIndex;
+use crate::model::{Grade, Skill, UserModel};
+
+/// The exercise picked to introduce a word of the word list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pick {
+    pub word: String,
+    // Position of the exercise in the index. None if no exercise has the word.
+    pub exercise: Option<usize>,
+    // The word was introduced by an earlier exercise, and is met again.
+    pub review: bool,
+}
+
+/// Pick an exercise for each word of the word list in turn, the way the trainer would for a user
+/// who learns every word of an exercise perfectly. Assumed words are known from the start.
+pub fn greedy(index: &ExerciseIndex, assumed_words: &[String]) -> Vec<Pick> {
+    let mut model = UserModel::new();
+    let now = Utc::now();
+    for word in assumed_words {
+        for skill in Skill::PINYIN {
+            model.review(word, skill, Grade::Perfect, now);
+        }
+    }
+    let mut picks = vec![];
+    loop {
+        let word = model.next_word(now, index.word_list());
+        if model.seen(&word) {
+            break;
+        }
+        for skill in Skill::PINYIN {
+            model.review(&word, skill, Grade::Perfect, now);
+        }
+
+        let exercise = model.next_exercise(now, index, &word);
+        if let Some(exercise) = exercise {
+            let exercise = &index.exercises()[exercise];
+            model.mark_seen(exercise, now);
+            for word in exercise.words() {
+                for skill in Skill::PINYIN {
+                    model.review(word, skill, Grade::Perfect, now);
+                }
+            }
+        }
+        picks.push(Pick {
+            word,
+            exercise,
+            review: false,
+        });
+    }
+    picks
+}
+
+/// The words a course makes the learner meet out of turn. Words outside the word list cost more
+/// than words met before their turn, since they are never practised on their own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CourseCost {
+    pub out_of_list: usize,
+    pub premature: usize,
+}
+
+impl std::ops::Add for CourseCost {
+    type Output = CourseCost;
+
+    fn add(self, other: CourseCost) -> CourseCost {
+        CourseCost {
+            out_of_list: self.out_of_list + other.out_of_list,
+            premature: self.premature + other.premature,
+        }
+    }
+}
+
+impl std::fmt::Display for CourseCost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} not in the word list, {} introduced before their turn",
+            self.out_of_list, self.premature
+        )
+    }
+}
+
+/// A course found by `optimize`, and how it compares to the greedy one.
+pub struct Optimized {
+    pub picks: Vec<Pick>,
+    pub cost: CourseCost,
+    pub greedy_cost: CourseCost,
+    // Widest beam searched within the time budget. 0 if no search finished.
+    pub beam_width: usize,
+}
+
+/// Search for the course that introduces the fewest words out of turn. Picking the cheapest
+/// exercise for each word in turn can force costly exercises for later words, so courses are
+/// built with a beam search that keeps the cheapest partial courses at every step. Searches with
+/// ever wider beams are run until the time budget is spent, and the cheapest course found is kept.
+/// The greedy course is kept if no search improves on it.
+pub fn optimize(
+    index: &ExerciseIndex,
+    assumed_words: &[String],
+    time_budget: Duration,
+) -> Optimized {
+    // A budget too large to add to the current time has no deadline.
+    let deadline = Instant::now().checked_add(time_budget);
+    let picks = greedy(index, assumed_words);
+    let greedy_cost = report(index, assumed_words, &picks, 1).totals.cost();
+    let mut best = Optimized {
+        picks,
+        cost: greedy_cost,
+        greedy_cost,
+        beam_width: 0,
+    };
+    let search = BeamSearch::new(index, assumed_words);
+    let mut width = 1;
+    while let Some((picks, cost, exhaustive)) = search.run(width, deadline) {
+        if cost < best.cost {
+            best.picks = picks;
+            best.cost = cost;
+        }
+        best.beam_width = width;
+        if exhaustive {
+            break;
+        }
+        width *= 2;
+    }
+    best
+}
+
+// Words are numbered so partial courses can keep the words they have introduced in a Vec<bool>.
+struct BeamSearch<'a> {
+    index: &'a ExerciseIndex,
+    // Word number of each word of the word list, in order.
+    word_list: Vec<usize>,
+    // Word numbers of the distinct words of each exercise.
+    exercises: Vec<Vec<usize>>,
+    in_list: Vec<bool>,
+    assumed: Vec<bool>,
+}
+
+// A partial course: the words it has introduced, and the last of its picks.
+struct BeamState {
+    seen: Vec<bool>,
+    // Position in the word list up to which every word has been seen.
+    next: usize,
+    cost: CourseCost,
+    node: Option<usize>,
+}
+
+impl<'a> BeamSearch<'a> {
+    fn new(index: &'a ExerciseIndex, assumed_words: &[String]) -> Self {
+        let mut numbers: HashMap<&str, usize> = HashMap::new();
+        let mut number = |word: &'a str| {
+            let next = numbers.len();
+            *numbers.entry(word).or_insert(next)
+        };
+        let word_list = index
+            .word_list()
+            .iter()
+            .map(|word| number(word))
+            .collect::<Vec<_>>();
+        let exercises = (0..index.exercises().len())
+            .map(|n| {
+                index
+                    .words(n)
+                    .iter()
+                    .map(|word| number(word))
+                    .unique()
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        let mut in_list = vec![false; numbers.len()];
+        for &word in &word_list {
+            in_list[word] = true;
+        }
+        let mut assumed = vec![false; numbers.len()];
+        for word in assumed_words {
+            if let Some(&word) = numbers.get(word.as_str()) {
+                assumed[word] = true;
+            }
+        }
+        BeamSearch {
+            index,
+            word_list,
+            exercises,
+            in_list,
+            assumed,
+        }
+    }
+
+    // Build courses word by word, keeping the `width` cheapest at every step. Returns the cheapest
+    // complete course and whether every course fit in the beam, or None if the deadline passes
+    // first.
+    fn run(
+        &self,
+        width: usize,
+        deadline: Option<Instant>,
+    ) -> Option<(Vec<Pick>, CourseCost, bool)> {
+        // Picks of all states, each linked to the pick before it.
+        let mut nodes: Vec<(Option<usize>, Pick)> = vec![];
+        let mut states = vec![BeamState {
+            seen: self.assumed.clone(),
+            next: 0,
+            cost: CourseCost::default(),
+            node: None,
+        }];
+        let mut best: Option<(CourseCost, Option<usize>)> = None;
+        let mut exhaustive = true;
+        while !states.is_empty() {
+            if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                return None;
+            }
+            // Cost of each way of extending each state: (cost, state, target position, exercise).
+            let mut children = vec![];
+            for (n, state) in states.iter_mut().enumerate() {
+                while state.next < self.word_list.len() && state.seen[self.word_list[state.next]] {
+                    state.next += 1;
+                }
+                let Some(&target) = self.word_list.get(state.next) else {
+                    if best.map_or(true, |(cost, _)| state.cost < cost) {
+                        best = Some((state.cost, state.node));
+                    }
+                    continue;
+                };
+                let candidates = self.index.with_word(&self.index.word_list()[state.next]);
+                if candidates.is_empty() {
+                    children.push((state.cost, n, state.next, None));
+                }
+                for &exercise in candidates {
+                    // Words with many exercises make a single step slow.
+                    if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                        return None;
+                    }
+                    let cost = state.cost + self.step_cost(&state.seen, target, exercise);
+                    children.push((cost, n, state.next, Some(exercise)));
+                }
+            }
+            children.sort_by_key(|&(cost, ..)| cost);
+            exhaustive &= children.len() <= width;
+            children.truncate(width);
+            states = children
+                .into_iter()
+                .map(|(cost, n, position, exercise)| {
+                    let mut seen = states[n].seen.clone();
+                    seen[self.word_list[position]] = true;
+                    for &word in exercise.map_or(&[][..], |e| &self.exercises[e]) {
+                        seen[word] = true;
+                    }
+                    let pick = Pick {
+                        word: self.index.word_list()[position].clone(),
+                        exercise,
+                        review: false,
+                    };
+                    nodes.push((states[n].node, pick));
+                    BeamState {
+                        seen,
+                        next: position + 1,
+                        cost,
+                        node: Some(nodes.len() - 1),
+                    }
+                })
+                .collect();
+        }
+
+        let (cost, mut node) = best?;
+        let mut picks = vec![];
+        while let Some(n) = node {
+            let (parent, pick) = &nodes[n];
+            picks.push(pick.clone());
+            node = *parent;
+        }
+        picks.reverse();
+        Some((picks, cost, exhaustive))
+    }
+
+    // Words an exercise introduces besides the target word.
+    fn step_cost(&self, seen: &[bool], target: usize, exercise: usize) -> CourseCost {
+        let mut cost = CourseCost::default();
+        for &word in &self.exercises[exercise] {
+            if word == target || seen[word] {
+                continue;
+            }
+            if self.in_list[word] {
+                cost.premature += 1;
+            } else {
+                cost.out_of_list += 1;
+            }
+        }
+        cost
+    }
+}
+
+/// Add exercises to a course until each word of the word list is met in at least `min_exposures`
+/// distinct exercises. Like reviews in the trainer, repetitions of a word are spaced out: the
+/// second exposure comes at least `spacing` exercises after the first, and each later gap is twice
+/// the one before. Words met in any exercise of the course count as exposed there.
+///
+/// Repetitions only use exercises whose words have all been introduced, so the words introduced
+/// by the picks are unchanged. Once every word has been introduced, the remaining repetitions
+/// follow each other as closely as they must. Words for which no such exercise is left keep fewer
+/// exposures.
+pub fn spread_exposures(
+    index: &ExerciseIndex,
+    assumed_words: &[String],
+    picks: Vec<Pick>,
+    min_exposures: usize,
+    spacing: usize,
+) -> Vec<Pick> {
+    let mut spread = Spread {
+        index,
+        min_exposures,
+        spacing,
+        used: picks.iter().filter_map(|pick| pick.exercise).collect(),
+        course: Course::new(assumed_words),
+        exposures: HashMap::new(),
+        due: HashMap::new(),
+        pending: BTreeSet::new(),
+        picks: vec![],
+    };
+    let mut intros = picks.into_iter().peekable();
+    loop {
+        let position = spread.course.exercises().len();
+        let next = spread
+            .pending
+            .first()
+            .filter(|(due, _)| *due <= position || intros.peek().is_none())
+            .cloned();
+        let Some((_, word)) = next else {
+            match intros.next() {
+                Some(pick) => spread.place(pick),
+                None => break,
+            }
+            continue;
+        };
+        spread.unschedule(&word);
+        match spread.review(&word) {
+            Some(exercise) => {
+                spread.used.insert(exercise);
+                spread.place(Pick {
+                    word,
+                    exercise: Some(exercise),
+                    review: true,
+                });
+            }
+            // Exercises for the word may become usable once more words have been introduced.
+            None if intros.peek().is_some() => {
+                spread.schedule(&word, position.saturating_add(spacing.max(1)))
+            }
+            None => {}
+        }
+    }
+    spread.picks
+}
+
+// State of spread_exposures while the course is built.
+struct Spread<'a> {
+    index: &'a ExerciseIndex,
+    min_exposures: usize,
+    spacing: usize,
+    // Exercises in the course or still to be placed.
+    used: HashSet<usize>,
+    course: Course,
+    // List word -> number of exercises it has been met in so far.
+    exposures: HashMap<String, usize>,
+    // Words that need more exposures -> position the next one is due at, and the same ordered by
+    // position.
+    due: HashMap<String, usize>,
+    pending: BTreeSet<(usize, String)>,
+    picks: Vec<Pick>,
+}
+
+impl Spread<'_> {
+    fn schedule(&mut self, word: &str, position: usize) {
+        self.due.insert(word.to_string(), position);
+        self.pending.insert((position, word.to_string()));
+    }
+
+    fn unschedule(&mut self, word: &str) {
+        if let Some(position) = self.due.remove(word) {
+            self.pending.remove(&(position, word.to_string()));
+        }
+    }
+
+    // Place an exercise at the end of the course, and schedule the next exposure of its words.
+    fn place(&mut self, pick: Pick) {
+        if let Some(exercise) = pick.exercise {
+            let position = self.course.exercises().len();
+            let exercise = &self.index.exercises()[exercise];
+            for word in exercise.words().into_iter().unique() {
+                if !self.index.in_list(word) {
+                    continue;
+                }
+                self.unschedule(word);
+                let count = self.exposures.entry(word.clone()).or_default();
+                *count += 1;
+                if *count < self.min_exposures {
+                    // Large exposure counts push the word past the end of the course.
+                    let doubling = u32::try_from(*count - 1)
+                        .ok()
+                        .and_then(|shift| 1usize.checked_shl(shift))
+                        .unwrap_or(usize::MAX);
+                    let gap = self.spacing.saturating_mul(doubling);
+                    self.schedule(word, position.saturating_add(gap));
+                }
+            }
+            self.course.push_exercise(exercise.clone());
+        }
+        self.picks.push(pick);
+    }
+
+    // An unused exercise meeting the word again without introducing new words. Prefers exercises
+    // that least disturb the spacing of their other words, then short ones.
+    fn review(&self, word: &str) -> Option<usize> {
+        let position = self.course.exercises().len();
+        self.index
+            .with_word(word)
+            .iter()
+            .copied()
+            .filter(|exercise| !self.used.contains(exercise))
+            .filter(|&exercise| {
+                let exercise = &self.index.exercises()[exercise];
+                self.course.new_words(word, exercise).is_empty()
+            })
+            .min_by_key(|&exercise| {
+                let exercise = &self.index.exercises()[exercise];
+                let early = exercise
+                    .words()
+                    .into_iter()
+                    .filter(|w| *w != word)
+                    .filter(|w| self.due.get(*w).is_some_and(|due| *due > position))
+                    .count();
+                (early, exercise.chinese().chars().count())
+            })
+    }
+}
+
+// Exercises picked so far, and the words they have introduced.
+pub struct Course {
+    course_exercises: Vec<Exercise>,
+    // Words of the course exercises, and words assumed to be known.
+    seen_words: HashSet<String>,
+}
+
+impl Course {
+    pub fn new(assumed_words: &[String]) -> Self {
+        Course {
+            course_exercises: vec![],
+            seen_words: assumed_words.iter().cloned().collect(),
+        }
+    }
+
+    pub fn push_exercise(&mut self, exercise: Exercise) {
+        self.seen_words
+            .extend(exercise.words().into_iter().cloned());
+        self.course_exercises.push(exercise);
+    }
+
+    pub fn exercises(&self) -> &[Exercise] {
+        &self.course_exercises
+    }
+
+    // Words the exercise would introduce besides the target word, in the order they occur.
+    pub fn new_words<'a>(&self, target_word: &str, exercise: &'a Exercise) -> Vec<&'a String> {
+        exercise
+            .words()
+            .into_iter()
+            .filter(|w| *w != target_word && !self.seen_words.contains(*w))
+            .collect::<Vec<_>>()
+    }
+}
+
+/// How well a course fits its word list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    // Words of the word list introduced by the exercise of an earlier word.
+    pub premature: Vec<Introduction>,
+    // Words that are neither in the word list nor assumed.
+    pub out_of_list: Vec<Introduction>,
+    // Words of the word list that no exercise has.
+    pub uncovered: Vec<String>,
+    // Words of the word list met in fewer exercises than asked for.
+    pub underexposed: Vec<Exposures>,
+    pub totals: Totals,
+}
+
+/// Number of exercises of the course a word is met in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Exposures {
+    pub word: String,
+    pub exposures: usize,
+}
+
+/// A word first met in the exercise picked for another word.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Introduction {
+    pub word: String,
+    // Word the exercise was picked for.
+    pub target: String,
+    pub exercise: String,
+    pub sentence: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub words: usize,
+    pub exercises: usize,
+    pub premature: usize,
+    pub out_of_list: usize,
+    pub uncovered: usize,
+    pub underexposed: usize,
+}
+
+impl Totals {
+    pub fn cost(&self) -> CourseCost {
+        CourseCost {
+            out_of_list: self.out_of_list,
+            premature: self.premature,
+        }
+    }
+}
+
+/// Report the words the picked exercises introduce too soon or needlessly, and the words they
+/// leave out or meet in fewer than `min_exposures` exercises.
+pub fn report(
+    index: &ExerciseIndex,
+    assumed_words: &[String],
+    picks: &[Pick],
+    min_exposures: usize,
+) -> Report {
+    let mut course = Course::new(assumed_words);
+    let mut report = Report {
+        premature: vec![],
+        out_of_list: vec![],
+        uncovered: vec![],
+        underexposed: vec![],
+        totals: Totals::default(),
+    };
+    let mut exposures: HashMap<&str, usize> = HashMap::new();
+    for pick in picks {
+        let Some(exercise) = pick.exercise else {
+            report.uncovered.push(pick.word.clone());
+            continue;
+        };
+        let exercise = &index.exercises()[exercise];
+        for word in course.new_words(&pick.word, exercise) {
+            let introduction = Introduction {
+                word: word.clone(),
+                target: pick.word.clone(),
+                exercise: exercise.id(),
+                sentence: exercise.chinese(),
+            };
+            if index.in_list(word) {
+                report.premature.push(introduction);
+            } else {
+                report.out_of_list.push(introduction);
+            }
+        }
+        for word in exercise.words().into_iter().unique() {
+            *exposures.entry(word).or_default() += 1;
+        }
+        course.push_exercise(exercise.clone());
+    }
+    for word in index.word_list().iter().unique() {
+        let exposures = exposures.get(word.as_str()).copied().unwrap_or_default();
+        if exposures > 0 && exposures < min_exposures {
+            report.underexposed.push(Exposures {
+                word: word.clone(),
+                exposures,
+            });
+        }
+    }
+    report.totals = Totals {
+        words: index.word_list().len(),
+        exercises: course.exercises().len(),
+        premature: report.premature.len(),
+        out_of_list: report.out_of_list.len(),
+        uncovered: report.uncovered.len(),
+        underexposed: report.underexposed.len(),
+    };
+    report
+}
+
+/// A line of the report in CSV form. Totals are rows of their own, with the count in the count
+/// column.
+#[derive(Debug, Serialize)]
+pub struct ReportRow<'a> {
+    pub kind: &'a str,
+    pub word: &'a str,
+    pub target: Option<&'a str>,
+    pub exercise: Option<&'a str>,
+    pub sentence: Option<&'a str>,
+    pub count: Option<usize>,
+}
+
+impl Report {
+    pub fn rows(&self) -> Vec<ReportRow<'_>> {
+        let total = |word, count| ReportRow {
+            kind: "total",
+            word,
+            target: None,
+            exercise: None,
+            sentence: None,
+            count: Some(count),
+        };
+        self.premature
+            .iter()
+            .map(|i| i.row("premature"))
+            .chain(self.out_of_list.iter().map(|i| i.row("out-of-list")))
+            .chain(self.uncovered.iter().map(|word| ReportRow {
+                kind: "uncovered",
+                word,
+                target: None,
+                exercise: None,
+                sentence: None,
+                count: None,
+            }))
+            .chain(self.underexposed.iter().map(|e| ReportRow {
+                kind: "underexposed",
+                word: &e.word,
+                target: None,
+                exercise: None,
+                sentence: None,
+                count: Some(e.exposures),
+            }))
+            .chain([
+                total("words", self.totals.words),
+                total("exercises", self.totals.exercises),
+                total("premature", self.totals.premature),
+                total("out-of-list", self.totals.out_of_list),
+                total("uncovered", self.totals.uncovered),
+                total("underexposed", self.totals.underexposed),
+            ])
+            .collect()
+    }
+}
+
+impl Introduction {
+    fn row<'a>(&'a self, kind: &'a str) -> ReportRow<'a> {
+        ReportRow {
+            kind,
+            word: &self.word,
+            target: Some(&self.target),
+            exercise: Some(&self.exercise),
+            sentence: Some(&self.sentence),
+            count: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sections = [
+            ("Introduced before their turn", &self.premature),
+            ("Not in the word list", &self.out_of_list),
+        ];
+        for (title, introductions) in sections {
+            if introductions.is_empty() {
+                continue;
+            }
+            writeln!(f, "{title}:")?;
+            for i in introductions {
+                writeln!(f, "  {} by {}: {}", i.word, i.target, i.sentence)?;
+            }
+        }
+        if !self.uncovered.is_empty() {
+            writeln!(f, "No exercises:")?;
+            writeln!(f, "  {}", self.uncovered.join(" "))?;
+        }
+        if !self.underexposed.is_empty() {
+            writeln!(f, "Too few exercises:")?;
+            for e in &self.underexposed {
+                writeln!(f, "  {} ({})", e.word, e.exposures)?;
+            }
+        }
+        let totals = &self.totals;
+        write!(
+            f,
+            "{} words, {} exercises, {} introduced before their turn, {} not in the word list, {} without exercises, {} with too few exercises.",
+            totals.words,
+            totals.exercises,
+            totals.premature,
+            totals.out_of_list,
+            totals.uncovered,
+            totals.underexposed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_report() {
+        let index = ExerciseIndex::new(
+            vec![
+                Exercise::from_words(&["我", "是", "学生"]),
+                Exercise::from_words(&["你", "好"]),
+                Exercise::from_words(&["我", "好"]),
+            ],
+            words(&["我", "你", "是", "学生", "猫"]),
+        );
+        let picks = greedy(&index, &words(&["好"]));
+        assert_eq!(
+            picks,
+            vec![
+                Pick {
+                    word: "我".to_string(),
+                    exercise: Some(2),
+                    review: false,
+                },
+                Pick {
+                    word: "你".to_string(),
+                    exercise: Some(1),
+                    review: false,
+                },
+                Pick {
+                    word: "是".to_string(),
+                    exercise: Some(0),
+                    review: false,
+                },
+                Pick {
+                    word: "猫".to_string(),
+                    exercise: None,
+                    review: false,
+                },
+            ]
+        );
+        let report = report(&index, &words(&["好"]), &picks, 1);
+        assert_eq!(
+            report.premature,
+            vec![Introduction {
+                word: "学生".to_string(),
+                target: "是".to_string(),
+                exercise: "我是学生".to_string(),
+                sentence: "我是学生".to_string(),
+            }]
+        );
+        assert_eq!(report.out_of_list, vec![]);
+        assert_eq!(report.uncovered, words(&["猫"]));
+        assert_eq!(
+            report.totals,
+            Totals {
+                words: 5,
+                exercises: 3,
+                premature: 1,
+                out_of_list: 0,
+                uncovered: 1,
+                underexposed: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_optimize_looks_ahead() {
+        // Both exercises for 我 have a word outside the list, but only 吗 is needed again later.
+        let index = ExerciseIndex::new(
+            vec![
+                Exercise::from_words(&["我", "呢"]),
+                Exercise::from_words(&["我", "吗"]),
+                Exercise::from_words(&["是", "吗"]),
+                Exercise::from_words(&["好", "吗"]),
+            ],
+            words(&["我", "是", "好"]),
+        );
+        let exercises = |picks: &[Pick]| picks.iter().map(|pick| pick.exercise).collect::<Vec<_>>();
+        assert_eq!(
+            exercises(&greedy(&index, &[])),
+            vec![Some(0), Some(2), Some(3)]
+        );
+        let optimized = optimize(&index, &[], Duration::from_millis(100));
+        assert_eq!(
+            optimized.greedy_cost,
+            CourseCost {
+                out_of_list: 2,
+                premature: 0,
+            }
+        );
+        assert_eq!(
+            optimized.cost,
+            CourseCost {
+                out_of_list: 1,
+                premature: 0,
+            }
+        );
+        assert_eq!(exercises(&optimized.picks), vec![Some(1), Some(2), Some(3)]);
+        assert!(optimized.beam_width >= 2);
+        assert_eq!(
+            report(&index, &[], &optimized.picks, 1).totals.cost(),
+            optimized.cost
+        );
+
+        // A budget too large for a deadline searches until every course fits in the beam.
+        let optimized = optimize(&index, &[], Duration::MAX);
+        assert_eq!(exercises(&optimized.picks), vec![Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn test_spread_exposures() {
+        let index = ExerciseIndex::new(
+            vec![
+                Exercise::from_words(&["我"]),
+                Exercise::from_words(&["是"]),
+                Exercise::from_words(&["我", "是"]),
+                Exercise::from_words(&["我", "好"]),
+            ],
+            words(&["我", "是"]),
+        );
+        let assumed = words(&["好"]);
+        let picks = greedy(&index, &assumed);
+        assert_eq!(
+            picks.iter().map(|pick| pick.exercise).collect::<Vec<_>>(),
+            vec![Some(0), Some(1)]
+        );
+        // 我 is met again before 是 is introduced, in the only exercise that has no new words.
+        let spread = spread_exposures(&index, &assumed, picks, 2, 1);
+        assert_eq!(
+            spread
+                .iter()
+                .map(|pick| (pick.word.as_str(), pick.exercise, pick.review))
+                .collect::<Vec<_>>(),
+            vec![
+                ("我", Some(0), false),
+                ("我", Some(3), true),
+                ("是", Some(1), false),
+                ("是", Some(2), true),
+            ]
+        );
+        let report = report(&index, &assumed, &spread, 3);
+        assert_eq!(report.totals.premature + report.totals.out_of_list, 0);
+        assert_eq!(
+            report.underexposed,
+            vec![Exposures {
+                word: "是".to_string(),
+                exposures: 2,
+            }]
+        );
+
+        // Gaps that no longer fit in a usize don't overflow.
+        let index = ExerciseIndex::new(vec![Exercise::from_words(&["我"]); 70], words(&["我"]));
+        for spacing in [1, usize::MAX / 2] {
+            let picks = greedy(&index, &[]);
+            assert_eq!(spread_exposures(&index, &[], picks, 100, spacing).len(), 70);
+        }
+    }
+}
@@ -1,14 +1,14 @@
-use std::{
-    fs::File,
-    io::{self, BufReader},
-};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader};
 
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use itertools::Itertools;
 use ratatui::{
     layout::Offset,
     prelude::*,
@@ -19,57 +19,315 @@ use rodio::{Decoder, OutputStream, Sink};
 use tui_input::{backend::crossterm::EventHandler, Input};
 
 use crate::{
-    convert::Exercise,
-    model::{ExerciseScore, UserModel},
+    convert::{strip_tone, Exercise, Segment},
+    ime::Ime,
+    index::ExerciseIndex,
+    model::{ExerciseScore, Grade, Skill, UserModel, WordListStatus},
+    profile::Profile,
+    readings::Overrides,
+    review_log::{Review, ReviewLog},
+    scheduler::SchedulerKind,
+    status::StatusTracker,
+    story::{shown_reading_grade, Bookmarks, Story},
 };
 
+// Answers that take longer than this are graded as slow.
+const SLOW_ANSWER_SECS: i64 = 10;
+
+// Playback speed of the slow replay in dictation mode.
+const SLOW_PLAYBACK_SPEED: f32 = 0.7;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    // See Chinese, type pinyin.
+    #[default]
+    Pinyin,
+    // See pinyin, type characters by picking them from the candidates for the typed pinyin.
+    Hanzi,
+    // Hear the sentence, type its pinyin. The Chinese is revealed as it is typed.
+    Dictation,
+    // See the English, produce the Chinese sentence by picking characters like in hanzi mode.
+    Production,
+    // See the sentence with pinyin, type the pinyin of the blanked target word only.
+    Cloze,
+}
+
+impl Mode {
+    // Skills graded by the mode. Words are scheduled by these skills only.
+    fn skills(self) -> &'static [Skill] {
+        match self {
+            Mode::Pinyin | Mode::Dictation | Mode::Cloze => &Skill::PINYIN,
+            Mode::Hanzi => &[Skill::Hanzi],
+            Mode::Production => &[Skill::Meaning],
+        }
+    }
+
+    // Whether answers are given by picking characters instead of typing pinyin.
+    fn picks_characters(self) -> bool {
+        matches!(self, Mode::Hanzi | Mode::Production)
+    }
+}
+
 struct App {
     _audio_stream: OutputStream,
-    _audio_sink: Sink,
-    word_list: Vec<String>,
+    audio_sink: Sink,
+    mode: Mode,
+    // In cloze mode, also blank the other words of the exercise that are due for review.
+    blank_due: bool,
+    // Only built in the modes that pick characters.
+    ime: Option<Ime>,
     model: UserModel,
+    // Progress through the word list, as of the last redraw.
+    status: WordListStatus,
+    status_tracker: StatusTracker,
+    review_log: ReviewLog,
     exercise_score: ExerciseScore,
     target_word: String,
-    exercises: Vec<Exercise>,
+    // The exercises and the word list.
+    exercises: ExerciseIndex,
     exercise: Exercise,
     index: usize,
+    // Segments the user has to answer. In cloze mode the others are shown and skipped.
+    blanks: Vec<bool>,
     input: Input,
     // show_english: bool,
     show_hint: bool,
+    // Worst mistakes made while typing the current segment, graded per skill. Picking characters
+    // is graded by hanzi_grade whichever skill the mode trains.
+    syllables_grade: Grade,
+    tones_grade: Grade,
+    hanzi_grade: Grade,
+    // Characters picked since the last answered segment. A picked word may run into the following
+    // segments, so any segmentation of the sentence is accepted.
+    hanzi: String,
+    // Last candidate picked that doesn't continue the sentence.
+    wrong_pick: Option<String>,
+    // When the current segment became the one to answer.
+    segment_start: DateTime<Utc>,
+    // Syllables of the last answer accepted with wrong tones, and whether each tone was wrong.
+    tone_feedback: Vec<(String, bool)>,
     history: Vec<Exercise>,
+    // Exercises are read in order instead of being picked by the scheduler.
+    story: Option<Story>,
+    // Lines the story view is scrolled up from the latest sentence.
+    scroll: usize,
+    // Readings accepted for words besides the pinyin of the exercise.
+    overrides: Overrides,
 }
 
 impl App {
-    fn new(word_list: Vec<String>, exercises: Vec<Exercise>) -> Self {
-        let model = UserModel::load().unwrap_or_default();
-        let target_word = model.next_word(Utc::now(), &word_list);
-        let exercise = model
-            .next_exercise(Utc::now(), &exercises, &word_list, &target_word)
-            .unwrap();
-        let exercise_score = model.score_exercise(Utc::now(), &exercise, &word_list);
+    fn new(
+        exercises: ExerciseIndex,
+        scheduler: SchedulerKind,
+        mode: Mode,
+        blank_due: bool,
+        story: Option<Story>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let model = UserModel::load()
+            .unwrap_or_default()
+            .with_scheduler(scheduler)
+            .with_profile(&Profile::load()?);
+        let target_word = model.next_word_for(Utc::now(), exercises.word_list(), mode.skills());
+        let position = match &story {
+            Some(story) => story.position,
+            None => model
+                .next_exercise_for(Utc::now(), &exercises, &target_word, mode.skills())
+                .ok_or_else(|| format!("No exercise has the word {target_word}."))?,
+        };
+        let exercise = exercises.exercises()[position].clone();
+        let exercise_score =
+            model.score_exercise_for(Utc::now(), &exercises, position, mode.skills());
+        let review_log = ReviewLog::open_default()?;
+        let mut status_tracker = StatusTracker::new(
+            &model,
+            &exercises,
+            mode.skills(),
+            &review_log.load()?,
+            Utc::now(),
+        );
+        let status = status_tracker.status(Utc::now());
+        // A resumed story shows what has been read before.
+        let history = match &story {
+            Some(story) => exercises.exercises()[..story.position].to_vec(),
+            None => vec![],
+        };
         let (stream, stream_handle) = OutputStream::try_default().unwrap();
-        App {
+        let mut app = App {
             _audio_stream: stream,
-            _audio_sink: Sink::try_new(&stream_handle).unwrap(),
-            word_list,
+            audio_sink: Sink::try_new(&stream_handle).unwrap(),
+            mode,
+            blank_due,
+            ime: mode
+                .picks_characters()
+                .then(|| Ime::new(exercises.exercises())),
             model,
+            status,
+            status_tracker,
+            review_log,
             exercise_score,
             target_word,
             exercises,
             exercise,
             index: 0,
+            blanks: vec![],
             input: Input::new("".into()),
             // show_english: false,
             show_hint: false,
-            history: vec![],
+            syllables_grade: Grade::Perfect,
+            tones_grade: Grade::Perfect,
+            hanzi_grade: Grade::Perfect,
+            hanzi: String::new(),
+            wrong_pick: None,
+            segment_start: Utc::now(),
+            tone_feedback: vec![],
+            history,
+            story,
+            scroll: 0,
+            overrides: Overrides::load()?,
+        };
+        app.choose_blanks();
+        if mode == Mode::Dictation {
+            app.play_audio(1.0);
+        }
+        Ok(app)
+    }
+
+    // Move on to the next exercise. Returns false when the end of the story has been reached.
+    fn next_exercise(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        self.model.mark_seen(&self.exercise, now);
+        self.status_tracker.seen(&self.exercise);
+        self.history.push(self.exercise.clone());
+        self.scroll = 0;
+        self.target_word =
+            self.model
+                .next_word_for(now, self.exercises.word_list(), self.mode.skills());
+        let position = if let Some(story) = &mut self.story {
+            story.position += 1;
+            let len = self.exercises.exercises().len();
+            let mut bookmarks = Bookmarks::load()?;
+            bookmarks.set(&story.name, story.position, len);
+            bookmarks.store()?;
+            if story.position >= len {
+                return Ok(false);
+            }
+            story.position
+        } else {
+            self.model
+                .next_exercise_for(now, &self.exercises, &self.target_word, self.mode.skills())
+                .ok_or_else(|| format!("No exercise has the word {}.", self.target_word))?
+        };
+        self.exercise_score =
+            self.model
+                .score_exercise_for(now, &self.exercises, position, self.mode.skills());
+        self.exercise = self.exercises.exercises()[position].clone();
+        self.choose_blanks();
+        self.segment_start = now;
+        if self.mode == Mode::Dictation {
+            self.play_audio(1.0);
+        }
+        Ok(true)
+    }
+
+    // Answers accepted for a segment: any of its readings, written in any accepted form.
+    fn accepted(&self, segment: &Segment) -> Vec<String> {
+        segment
+            .readings()
+            .flat_map(|reading| self.overrides.accepted(&segment.chinese, reading))
+            .unique()
+            .collect()
+    }
+
+    // Pinyin shown next to a word in a story. Only words that are not known yet get a reading, and
+    // only when the answer is typed as pinyin.
+    fn reading(&self, segment: &Segment) -> Option<String> {
+        let story = self.story.as_ref().filter(|_| self.mode == Mode::Pinyin)?;
+        if segment.pinyin.is_empty()
+            || !story.shows_reading(&self.model, &segment.chinese, self.mode.skills())
+        {
+            None
+        } else {
+            Some(segment.pinyin.replace(' ', ""))
         }
     }
+
+    // Decide which segments of the current exercise to ask for and move to the first of them.
+    fn choose_blanks(&mut self) {
+        let now = Utc::now();
+        self.blanks = self
+            .exercise
+            .segments
+            .iter()
+            .map(|s| {
+                self.mode != Mode::Cloze
+                    || !s.pinyin.is_empty()
+                        && (s.chinese == self.target_word
+                            || self.blank_due
+                                && self.model.is_due(&s.chinese, self.mode.skills(), now))
+            })
+            .collect();
+        self.index = 0;
+        self.skip_shown();
+    }
+
+    // Move past segments that don't have to be answered.
+    fn skip_shown(&mut self) {
+        while self.index < self.blanks.len() && !self.blanks[self.index] {
+            self.index += 1;
+        }
+    }
+
+    // Play the audio of the current exercise from the start. The audio is cached by the audio
+    // command.
+    fn play_audio(&self, speed: f32) {
+        let Ok(file) = File::open(crate::audio_file_name(&self.exercise.chinese())) else {
+            return;
+        };
+        let Ok(source) = Decoder::new(BufReader::new(file)) else {
+            return;
+        };
+        self.audio_sink.clear();
+        self.audio_sink.set_speed(speed);
+        self.audio_sink.append(source);
+        self.audio_sink.play();
+    }
 }
 
 pub fn train(
-    word_list: Vec<String>,
+    mut word_list: Vec<String>,
     mut exercises: Vec<Exercise>,
+    scheduler: SchedulerKind,
+    mode: Mode,
+    blank_due: bool,
+    story: Option<Story>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // A story is read in full, so missing audio is checked before it starts.
+    if mode == Mode::Dictation && story.is_none() {
+        exercises.retain(|e| crate::audio_file_name(&e.chinese()).exists());
+        if exercises.is_empty() {
+            return Err("No audio for any exercise. Run the audio command first.".into());
+        }
+        let words = exercises
+            .iter()
+            .flat_map(|e| e.words())
+            .cloned()
+            .collect::<HashSet<_>>();
+        word_list.retain(|w| words.contains(w));
+        if word_list.is_empty() {
+            return Err("No audio for any exercise with a word of the word list.".into());
+        }
+    }
+    if word_list.is_empty() {
+        return Err("The word list is empty.".into());
+    }
+
+    // create app before the terminal is taken over, so errors are shown normally
+    if story.is_none() {
+        exercises.reverse();
+    }
+    let exercises = ExerciseIndex::new(exercises, word_list);
+    let app = App::new(exercises, scheduler, mode, blank_due, story)?;
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -77,9 +335,7 @@ pub fn train(
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    exercises.reverse();
-    let app = App::new(word_list, exercises);
+    // run app
     let res = run_app(&mut terminal, app);
 
     // restore terminal
@@ -98,125 +354,389 @@ pub fn train(
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+) -> Result<(), Box<dyn std::error::Error>> {
     loop {
+        app.status = app.status_tracker.status(Utc::now());
         terminal.draw(|f| ui(f, &app))?;
 
         let evt = event::read()?;
+        // Enter accepts an answer with the right syllables but wrong tones.
+        let mut submitted = false;
+        let mut handled = false;
         if let Event::Key(key) = &evt {
             match key.code {
                 KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
                     return Ok(())
                 }
+                KeyCode::Char('r')
+                    if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                        && app.mode == Mode::Dictation =>
+                {
+                    app.play_audio(1.0);
+                    handled = true;
+                }
+                KeyCode::Char('s')
+                    if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                        && app.mode == Mode::Dictation =>
+                {
+                    app.play_audio(SLOW_PLAYBACK_SPEED);
+                    handled = true;
+                }
+                KeyCode::PageUp if app.story.is_some() => {
+                    // Each sentence takes three lines of the story view.
+                    app.scroll = (app.scroll + 1).min(app.history.len() * 3);
+                    handled = true;
+                }
+                KeyCode::PageDown if app.story.is_some() => {
+                    app.scroll = app.scroll.saturating_sub(1);
+                    handled = true;
+                }
                 KeyCode::Esc => {
                     app.show_hint = true;
                 }
+                KeyCode::Enter => {
+                    submitted = true;
+                }
                 _ => {}
             }
+            if app.mode.picks_characters() {
+                handled = handle_hanzi_key(&mut app, key);
+            }
+        }
+        if !handled {
+            app.input.handle_event(&evt);
         }
-        app.input.handle_event(&evt);
 
-        let cursor = app.input.cursor();
-        let pinyin = apply_tones(app.input.value());
-        let pinyin_len = pinyin.chars().count();
-        app.input = Input::new(pinyin)
-            .with_cursor(cursor - (app.input.value().chars().count() - pinyin_len));
+        if !app.mode.picks_characters() {
+            let cursor = app.input.cursor();
+            let pinyin = apply_tones(app.input.value());
+            let pinyin_len = pinyin.chars().count();
+            app.input = Input::new(pinyin)
+                .with_cursor(cursor - (app.input.value().chars().count() - pinyin_len));
+
+            if let Some(target) = app.exercise.segments.get(app.index) {
+                let accepted = app.accepted(target);
+                let mistake = accepted
+                    .iter()
+                    .map(|pinyin| classify_input(app.input.value(), pinyin))
+                    .min()
+                    .flatten();
+                match mistake {
+                    Some(Grade::WrongTone) => app.tones_grade = Grade::WrongTone,
+                    Some(mistake) => app.syllables_grade = app.syllables_grade.max(mistake),
+                    None => {}
+                }
+            }
+        }
 
         while app.index < app.exercise.segments.len() {
             let target = &app.exercise.segments[app.index];
-            if target
-                .pinyin
-                .to_lowercase()
-                .replace(char::is_whitespace, "")
-                == app
-                    .input
-                    .value()
-                    .trim()
-                    .to_lowercase()
-                    .replace(char::is_whitespace, "")
-            {
+            let accepted = app.accepted(target);
+            let tones = if submitted {
+                closest_tones(app.input.value(), &accepted)
+            } else {
+                None
+            };
+            let answered = match app.mode {
+                Mode::Pinyin | Mode::Dictation | Mode::Cloze => {
+                    accepted.iter().any(|pinyin| {
+                        normalize_pinyin(pinyin) == normalize_pinyin(app.input.value())
+                    }) || tones.is_some()
+                }
+                Mode::Hanzi | Mode::Production => {
+                    target.pinyin.is_empty() || app.hanzi.starts_with(&target.chinese)
+                }
+            };
+            if answered {
+                submitted = false;
+                if let Some((pinyin, tones)) =
+                    tones.filter(|(_, tones)| tones.iter().any(|&wrong| wrong))
+                {
+                    app.tones_grade = Grade::WrongTone;
+                    app.tone_feedback = pinyin
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .zip(tones)
+                        .collect();
+                } else if !target.pinyin.is_empty() {
+                    app.tone_feedback.clear();
+                }
                 if !target.pinyin.is_empty() {
                     let now = Utc::now();
-                    let prof = app
-                        .model
-                        .with_proficiency(&app.exercise.segments[app.index].chinese, now);
-                    if app.show_hint {
-                        // Reset memory strength
-                        prof.fail(now);
-                    } else {
-                        // Increase memory strength
-                        prof.success(now);
+                    let slow = now - app.segment_start > Duration::seconds(SLOW_ANSWER_SECS);
+                    let (grades, input) = match app.mode {
+                        Mode::Pinyin | Mode::Dictation | Mode::Cloze => (
+                            vec![
+                                (Skill::Syllables, app.syllables_grade),
+                                (Skill::Tones, app.tones_grade),
+                            ],
+                            app.input.value().to_string(),
+                        ),
+                        Mode::Hanzi => (
+                            vec![(Skill::Hanzi, app.hanzi_grade)],
+                            target.chinese.clone(),
+                        ),
+                        Mode::Production => (
+                            vec![(Skill::Meaning, app.hanzi_grade)],
+                            target.chinese.clone(),
+                        ),
+                    };
+                    let hinted = app.show_hint;
+                    let shown = app.reading(target).is_some();
+                    for (skill, mut grade) in grades {
+                        if hinted {
+                            grade = grade.max(Grade::Hinted);
+                        }
+                        if shown {
+                            grade = shown_reading_grade(grade);
+                        }
+                        if slow {
+                            grade = grade.max(Grade::Slow);
+                        }
+                        app.model.review(&target.chinese, skill, grade, now);
+                        app.review_log.append(&Review {
+                            timestamp: now,
+                            word: target.chinese.clone(),
+                            skill: Some(skill),
+                            exercise: app.exercise.id(),
+                            input: input.clone(),
+                            grade,
+                            latency_ms: (now - app.segment_start).num_milliseconds(),
+                            hint_used: hinted,
+                        })?;
                     }
                     app.model.store().unwrap();
+                    app.status_tracker
+                        .reviewed(&app.model, &app.exercises, &target.chinese, now);
                 }
                 app.index += 1;
                 app.input = Input::new("".into());
                 app.show_hint = false;
+                app.syllables_grade = Grade::Perfect;
+                app.tones_grade = Grade::Perfect;
+                app.hanzi_grade = Grade::Perfect;
+                if !target.pinyin.is_empty() {
+                    app.hanzi = app.hanzi.split_off(target.chinese.len());
+                }
+                app.wrong_pick = None;
+                app.skip_shown();
+                app.segment_start = Utc::now();
             } else {
                 break;
             }
         }
-        if app.index >= app.exercise.segments.len() {
-            // {
-            //     let clean_name = app
-            //         .exercise
-            //         .chinese()
-            //         .chars()
-            //         .filter(|c| c.is_alphanumeric())
-            //         .collect::<String>();
-            //     // dbg!(&clean_name);
-            //     let file = BufReader::new(File::open(format!("audio/{}.mp3", clean_name)).unwrap());
-            //     // Decode that sound file into a source
-            //     let source = Decoder::new(file).unwrap();
-            //     app.audio_sink.append(source);
-            // }
-            app.model.mark_seen(&app.exercise, Utc::now());
-            app.history.push(app.exercise.clone());
-            app.target_word = app.model.next_word(Utc::now(), &app.word_list);
-            let exercise = app
-                .model
-                .next_exercise(Utc::now(), &app.exercises, &app.word_list, &app.target_word)
-                .unwrap();
-            app.exercise_score = app
-                .model
-                .score_exercise(Utc::now(), &exercise, &app.word_list);
-            app.exercise = exercise;
-            app.index = 0;
+        if app.index >= app.exercise.segments.len() && !app.next_exercise()? {
+            return Ok(());
         }
     }
 }
 
+// Keys with a meaning of their own when picking characters: 1-9 pick a candidate for the typed
+// pinyin, space picks the first one, and backspace with no pinyin typed takes back the last
+// character. Characters typed with the system's input method are picked directly. Returns whether
+// the key was handled.
+fn handle_hanzi_key(app: &mut App, key: &KeyEvent) -> bool {
+    let pick = match key.code {
+        KeyCode::Char(' ') => Some(0),
+        KeyCode::Char(c @ '1'..='9') => Some(c as usize - '1' as usize),
+        KeyCode::Char(c) if is_hanzi(c) => None,
+        KeyCode::Backspace if app.input.value().is_empty() => {
+            app.hanzi.pop();
+            return true;
+        }
+        _ => return false,
+    };
+    let word = match (pick, key.code) {
+        (Some(pick), _) => app.ime.as_ref().and_then(|ime| {
+            ime.candidates(app.input.value())
+                .get(pick)
+                .map(|w| w.to_string())
+        }),
+        (None, KeyCode::Char(c)) => Some(c.to_string()),
+        _ => None,
+    };
+    let Some(word) = word else {
+        return true;
+    };
+    let rest = app
+        .exercise
+        .segments
+        .iter()
+        .skip(app.index)
+        .skip_while(|s| s.pinyin.is_empty())
+        .map(|s| s.chinese.as_str())
+        .collect::<String>();
+    let continues = rest
+        .strip_prefix(app.hanzi.as_str())
+        .is_some_and(|rest| rest.starts_with(&word));
+    if continues {
+        app.hanzi += &word;
+        app.input = Input::new("".into());
+        app.wrong_pick = None;
+    } else {
+        app.hanzi_grade = Grade::Wrong;
+        app.wrong_pick = Some(word);
+    }
+    true
+}
+
+fn is_hanzi(c: char) -> bool {
+    ('\u{4e00}'..='\u{9fff}').contains(&c)
+}
+
+// For each syllable of the expected pinyin, whether the answer has the wrong tone for it. Returns
+// None unless the answer has exactly the expected syllables.
+//
+// wrong_tones("xuésheng", "xué sheng") -> Some([false, false])
+// wrong_tones("xuěshēng", "xué sheng") -> Some([true, true])
+// wrong_tones("xuéshen", "xué sheng") -> None
+fn wrong_tones(input: &str, target: &str) -> Option<Vec<bool>> {
+    let mut input = normalize_pinyin(input)
+        .chars()
+        .collect::<Vec<_>>()
+        .into_iter();
+    let mut wrong = vec![];
+    for syllable in target.to_lowercase().split_whitespace() {
+        let answer = input
+            .by_ref()
+            .take(syllable.chars().count())
+            .collect::<String>();
+        if answer
+            .chars()
+            .map(strip_tone)
+            .ne(syllable.chars().map(strip_tone))
+        {
+            return None;
+        }
+        wrong.push(answer != syllable);
+    }
+    if input.next().is_some() {
+        return None;
+    }
+    Some(wrong)
+}
+
+// The accepted reading the answer has the fewest wrong tones for, with wrong_tones for it.
+//
+// closest_tones("yídìng", ["yī dìng", "yí dìng"]) -> Some(("yí dìng", [false, false]))
+fn closest_tones<'a>(input: &str, accepted: &'a [String]) -> Option<(&'a str, Vec<bool>)> {
+    accepted
+        .iter()
+        .filter_map(|pinyin| Some((pinyin.as_str(), wrong_tones(input, pinyin)?)))
+        .min_by_key(|(_, tones)| tones.iter().filter(|&&wrong| wrong).count())
+}
+
+fn normalize_pinyin(pinyin: &str) -> String {
+    pinyin
+        .trim()
+        .to_lowercase()
+        .replace(char::is_whitespace, "")
+}
+
+// Classify a (possibly partial) answer against the expected pinyin. Returns the mistake made so
+// far, if any. Syllables without a tone mark are not judged yet since the tone may still be typed.
+//
+// classify_input("xue", "xuésheng") -> None
+// classify_input("xuě", "xuésheng") -> Some(WrongTone)
+// classify_input("xuo", "xuésheng") -> Some(Typo)
+fn classify_input(input: &str, target: &str) -> Option<Grade> {
+    let input = normalize_pinyin(input).chars().collect::<Vec<_>>();
+    let target = normalize_pinyin(target).chars().collect::<Vec<_>>();
+    if input.len() > target.len()
+        || input
+            .iter()
+            .zip(&target)
+            .any(|(&i, &t)| strip_tone(i) != strip_tone(t))
+    {
+        return Some(Grade::Typo);
+    }
+    if input
+        .iter()
+        .zip(&target)
+        .any(|(&i, &t)| i != t && strip_tone(i) != i)
+    {
+        return Some(Grade::WrongTone);
+    }
+    None
+}
+
 fn ui(f: &mut Frame, app: &App) {
     let vertical = Layout::vertical([
         Constraint::Length(1), // Status: target word
         Constraint::Length(1), // Exercise score
-        Constraint::Length(1), // Chinese
-        Constraint::Length(1), // Pinyin
+        Constraint::Length(1), // Prompt
+        Constraint::Length(1), // Reading
+        Constraint::Length(1), // Answer
         Constraint::Length(1), // Hint
+        Constraint::Length(1), // Candidates
         Constraint::Min(1),    // History
     ]);
-    let [status_area, exercise_score_area, help_area, pinyin_area, hint_area, messages_area] =
+    let [status_area, exercise_score_area, help_area, reading_area, answer_area, hint_area, candidates_area, messages_area] =
         vertical.areas(f.size());
 
-    let model_status = app.model.status(&app.exercises, &app.word_list, Utc::now());
+    let model_status = &app.status;
+    let position = match &app.story {
+        Some(story) => format!(
+            "Sentence: {}/{}",
+            story.position + 1,
+            app.exercises.exercises().len()
+        ),
+        None => format!("Target word: {}", app.target_word),
+    };
     let status = Paragraph::new(format!(
-        "Target word: {}, known words: {}, to review: {}, total: {}, sentences: {}/{}",
-        app.target_word,
+        "{}, known words: {}, to review: {} ({} within the hour), total: {}, sentences: {}/{} ({} unlocked today)",
+        position,
         model_status.known_words,
         model_status.words_to_review,
+        model_status.words_due_soon,
         model_status.total_words,
         model_status.seen_sentences,
-        model_status.unlocked_sentences
+        model_status.unlocked_sentences,
+        model_status.unlocked_today
     ));
     f.render_widget(status, status_area);
 
     let exercise_score = Paragraph::new(format!("Exercise score: {:?}", app.exercise_score));
     f.render_widget(exercise_score, exercise_score_area);
 
+    // The prompt shows the whole sentence, the answer line what has been answered so far.
+    let (prompt_label, answer_label) = match app.mode {
+        Mode::Pinyin | Mode::Dictation | Mode::Cloze => ("Chinese: ", "Pinyin:  "),
+        Mode::Hanzi => ("Pinyin:  ", "Chinese: "),
+        Mode::Production => ("English: ", "Chinese: "),
+    };
     let mut msg = vec![];
-    msg.push("Chinese: ".into());
+    msg.push(prompt_label.into());
+    let mut reading: Vec<Span> = vec![" ".repeat(prompt_label.len()).into()];
+    if app.mode == Mode::Production {
+        msg.push(app.exercise.english.clone().into());
+    }
     for (nth, segment) in app.exercise.segments.iter().enumerate() {
-        let span: Span = segment.chinese.clone().into();
+        let span: Span = match app.mode {
+            Mode::Production => break,
+            // Unknown words of a story have their pinyin under them.
+            Mode::Pinyin if app.story.is_some() => {
+                let pinyin = app.reading(segment).unwrap_or_default();
+                let chinese_width = Span::raw(&segment.chinese).width();
+                let pinyin_width = Span::raw(&pinyin).width();
+                let width = chinese_width.max(pinyin_width) + 1;
+                reading.push(format!("{pinyin}{}", " ".repeat(width - pinyin_width)).into());
+                format!("{}{}", segment.chinese, " ".repeat(width - chinese_width)).into()
+            }
+            Mode::Pinyin => segment.chinese.clone().into(),
+            // The sentence is only heard until it has been typed.
+            Mode::Dictation if nth >= app.index => break,
+            Mode::Dictation => segment.chinese.clone().into(),
+            Mode::Cloze if app.blanks[nth] && nth >= app.index => {
+                "＿".repeat(segment.chinese.chars().count()).into()
+            }
+            Mode::Cloze => segment.chinese.clone().into(),
+            Mode::Hanzi if segment.pinyin.is_empty() => segment.chinese.clone().into(),
+            Mode::Hanzi => format!("{} ", segment.pinyin.replace(' ', "")).into(),
+        };
         if nth == app.index {
             msg.push(span.bold().fg(Color::Yellow));
         } else {
@@ -226,40 +746,111 @@ fn ui(f: &mut Frame, app: &App) {
     let text = Text::from(Line::from(msg));
     let help_message = Paragraph::new(text);
     f.render_widget(help_message, help_area);
+    f.render_widget(Paragraph::new(Line::from(reading)).dim(), reading_area);
 
-    let mut pinyin_msgs: Vec<Span> = vec![];
-    pinyin_msgs.push("Pinyin:  ".into());
+    let mut answer_msgs: Vec<Span> = vec![];
+    answer_msgs.push(answer_label.into());
     for segment in app.exercise.segments.iter().take(app.index) {
-        let span: Span = segment.pinyin.clone().replace(" ", "").into();
-        pinyin_msgs.push(span.dim());
-        pinyin_msgs.push(" ".into());
+        match app.mode {
+            Mode::Pinyin | Mode::Dictation | Mode::Cloze => {
+                let span: Span = segment.pinyin.clone().replace(' ', "").into();
+                answer_msgs.push(span.dim());
+                answer_msgs.push(" ".into());
+            }
+            Mode::Hanzi | Mode::Production => {
+                let span: Span = segment.chinese.clone().into();
+                answer_msgs.push(span.dim());
+            }
+        }
     }
-    let pinyin_line = Line::from(pinyin_msgs);
-    let pinyin_line_len = pinyin_line.width();
-    f.render_widget(Text::from(pinyin_line), pinyin_area);
+    if app.mode.picks_characters() {
+        answer_msgs.push(app.hanzi.clone().into());
+    }
+    let answer_line = Line::from(answer_msgs);
+    let answer_line_len = answer_line.width();
+    f.render_widget(Text::from(answer_line), answer_area);
 
-    let pinyin_area = pinyin_area.offset(Offset {
-        x: pinyin_line_len as i32,
+    let answer_area = answer_area.offset(Offset {
+        x: answer_line_len as i32,
         y: 0,
     });
     let input = Paragraph::new(app.input.value());
-    f.render_widget(input, pinyin_area);
+    f.render_widget(input, answer_area);
     // Make the cursor visible and ask ratatui to put it at the specified coordinates after
     // rendering
     #[allow(clippy::cast_possible_truncation)]
     f.set_cursor(
         // Draw the cursor at the current position in the input field.
         // This position is can be controlled via the left and right arrow key
-        pinyin_area.x + app.input.visual_cursor() as u16,
+        answer_area.x + app.input.visual_cursor() as u16,
         // Move one line down, from the border to the input line
-        pinyin_area.y,
+        answer_area.y,
     );
 
+    if app.mode == Mode::Cloze {
+        // The rest of the sentence follows the answer being typed.
+        let mut spans: Vec<Span> = vec![" ".into()];
+        for (nth, segment) in app.exercise.segments.iter().enumerate().skip(app.index + 1) {
+            let span: Span = if app.blanks[nth] {
+                "＿".into()
+            } else {
+                segment.pinyin.replace(' ', "").into()
+            };
+            spans.push(span.dim());
+            spans.push(" ".into());
+        }
+        let rest_area = answer_area
+            .offset(Offset {
+                x: Span::raw(app.input.value()).width() as i32,
+                y: 0,
+            })
+            .intersection(f.size());
+        f.render_widget(Paragraph::new(Line::from(spans)), rest_area);
+    }
+
+    if let Some(ime) = &app.ime {
+        let mut spans: Vec<Span> = vec![];
+        for (nth, word) in ime.candidates(app.input.value()).iter().enumerate() {
+            let span: Span = format!("{}.{} ", nth + 1, word).into();
+            spans.push(if app.wrong_pick.as_deref() == Some(word) {
+                span.fg(Color::Red)
+            } else {
+                span
+            });
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), candidates_area);
+    } else if app.mode == Mode::Dictation {
+        let keys = Paragraph::new("Ctrl-R: replay, Ctrl-S: replay slowly").dim();
+        f.render_widget(keys, candidates_area);
+    }
+
     if app.show_hint {
-        let hint = app.exercise.segments[app.index].pinyin.clone();
+        let segment = &app.exercise.segments[app.index];
+        let hint = match app.mode {
+            Mode::Pinyin | Mode::Dictation | Mode::Cloze => segment.pinyin.clone(),
+            Mode::Hanzi => segment.chinese.clone(),
+            Mode::Production => format!("{} {}", segment.chinese, segment.pinyin),
+        };
         let hint =
             Paragraph::new(format!("Answer: {hint}")).style(Style::default().fg(Color::Yellow));
         f.render_widget(hint, hint_area);
+    } else if !app.tone_feedback.is_empty() {
+        let mut spans: Vec<Span> = vec!["Tones:   ".into()];
+        for (syllable, wrong) in &app.tone_feedback {
+            let span: Span = syllable.clone().into();
+            spans.push(if *wrong {
+                span.bold().fg(Color::Red)
+            } else {
+                span.dim()
+            });
+            spans.push(" ".into());
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), hint_area);
+    }
+
+    if app.story.is_some() {
+        story_view(f, app, messages_area);
+        return;
     }
 
     let mut messages: Vec<ListItem> = vec![];
@@ -277,7 +868,7 @@ fn ui(f: &mut Frame, app: &App) {
             exercise
                 .segments
                 .iter()
-                .map(|s| s.pinyin.replace(" ", ""))
+                .map(|s| s.pinyin.replace(' ', ""))
                 .collect::<Vec<_>>()
                 .join(" ")
         ))));
@@ -291,6 +882,25 @@ fn ui(f: &mut Frame, app: &App) {
     f.render_widget(messages, messages_area);
 }
 
+// The sentences read so far, oldest first, with the latest at the bottom of the view.
+fn story_view(f: &mut Frame, app: &App, area: Rect) {
+    let mut lines: Vec<ListItem> = vec![];
+    for exercise in &app.history {
+        lines.push(ListItem::new(Text::from(exercise.chinese())));
+        lines.push(ListItem::new(Text::from(exercise.english.clone()).dim()));
+        lines.push(ListItem::new(Text::from("")));
+    }
+    let height = area.height.saturating_sub(2) as usize;
+    let end = lines
+        .len()
+        .saturating_sub(app.scroll)
+        .max(height.min(lines.len()));
+    let start = end.saturating_sub(height);
+    let lines = lines.drain(start..end).collect::<Vec<_>>();
+    let story = List::new(lines).block(Block::bordered().title("Story"));
+    f.render_widget(story, area);
+}
+
 // Apply tones to pinyin. The pinyin may cover multiple characters. Tone numbers
 // 1-4 apply to the first word without an existing tone mark. Tone 5 applies to
 // the last word _with_ a tone mark. For example, "xuésheng1" becomes "xuéshēng"
@@ -355,12 +965,11 @@ fn apply_tones(pinyin: &str) -> String {
     }
 
     // Recombine and prettify
-    let combined = chunks
+    chunks
         .into_iter()
         .map(|s| prettify_pinyin::prettify(&s))
         .collect::<Vec<_>>()
-        .join("");
-    combined
+        .join("")
 }
 
 // Best-effort word splitting. When this function does a bad job, one can always
@@ -484,6 +1093,65 @@ mod tests {
         assert_eq!(apply_tones("ma"), "ma");
     }
 
+    #[test]
+    fn test_classify_input() {
+        assert_eq!(classify_input("", "xué sheng"), None);
+        assert_eq!(classify_input("xue", "xué sheng"), None);
+        assert_eq!(classify_input("xué", "xué sheng"), None);
+        assert_eq!(classify_input("xuésheng", "xué sheng"), None);
+        assert_eq!(classify_input("xuě", "xué sheng"), Some(Grade::WrongTone));
+        assert_eq!(
+            classify_input("xuéshēng", "xué sheng"),
+            Some(Grade::WrongTone)
+        );
+        assert_eq!(classify_input("xuo", "xué sheng"), Some(Grade::Typo));
+        assert_eq!(classify_input("xuéshengg", "xué sheng"), Some(Grade::Typo));
+        assert_eq!(classify_input("Wǒ", "wǒ"), None);
+    }
+
+    #[test]
+    fn test_wrong_tones() {
+        assert_eq!(
+            wrong_tones("xuésheng", "xué sheng"),
+            Some(vec![false, false])
+        );
+        assert_eq!(
+            wrong_tones("xué sheng", "xué sheng"),
+            Some(vec![false, false])
+        );
+        assert_eq!(
+            wrong_tones("xuěsheng", "xué sheng"),
+            Some(vec![true, false])
+        );
+        assert_eq!(
+            wrong_tones("xuéshēng", "xué sheng"),
+            Some(vec![false, true])
+        );
+        assert_eq!(
+            wrong_tones("xuesheng", "xué sheng"),
+            Some(vec![true, false])
+        );
+        assert_eq!(wrong_tones("Wo", "wǒ"), Some(vec![true]));
+        assert_eq!(wrong_tones("xuéshen", "xué sheng"), None);
+        assert_eq!(wrong_tones("xuéshengs", "xué sheng"), None);
+        assert_eq!(wrong_tones("xiésheng", "xué sheng"), None);
+        assert_eq!(wrong_tones("", ""), Some(vec![]));
+    }
+
+    #[test]
+    fn test_closest_tones() {
+        let accepted = ["yī dìng".to_string(), "yí dìng".to_string()];
+        assert_eq!(
+            closest_tones("yídìng", &accepted),
+            Some(("yí dìng", vec![false, false]))
+        );
+        assert_eq!(
+            closest_tones("yǐdìng", &accepted),
+            Some(("yī dìng", vec![true, false]))
+        );
+        assert_eq!(closest_tones("yídì", &accepted), None);
+    }
+
     #[test]
     fn test_split_words_examples() {
         assert_eq!(split_words("xuesheng"), vec!["xue", "sheng"]);